@@ -1,33 +1,93 @@
+use std::path::PathBuf;
+
 use tui::{
     backend::Backend,
     layout::{Constraint, Direction, Layout, Alignment, Rect},
-    widgets::Paragraph,
+    widgets::{Paragraph, Block, Borders, Clear, Wrap},
     Frame, text::{Span, Spans}, style::{Style, Color}
 };
 
-use crate::{paths::Paths, backup::Backup};
+use crate::{paths::{Paths, BackupPath, centered_rect}, backup::Backup, storage::Snapshot, history::History, restore::Restore, error::Error};
 
 pub struct App {
     pub tabs: Vec<&'static str>,
     pub current_tab: usize,
     pub paths: Paths,
-    pub backup: Backup
+    pub backup: Backup,
+    pub history: History,
+    /// Shown on top of everything until a key is pressed.
+    pub error: Option<Error>
 }
 impl App {
     pub fn new() -> Self {
+        let mut error = None;
+        let paths = Paths::read().unwrap_or_else(|e| { error = Some(e); Paths::new(Vec::new()) });
+        let backup = Backup::read().unwrap_or_else(|e| { error.get_or_insert(e); Backup::new() });
+        Self { error, ..Self::with(paths, backup) }
+    }
+    /// An app on the first tab for already loaded settings.
+    pub fn with(paths: Paths, backup: Backup) -> Self {
         Self {
-            tabs: vec!["Menu","Paths","Backup"],
+            tabs: vec!["Menu","Paths","Backup","History"],
             current_tab: 0,
-            paths: Paths::read(),
-            backup: Backup::new()
+            paths,
+            backup,
+            history: History::new(),
+            error: None
         }
     }
+    pub fn report(&mut self, result: Result<(), Error>) {
+        if let Err(e) = result { self.error = Some(e) }
+    }
     pub fn next(&mut self) {
-        self.current_tab = (self.current_tab + 1) % self.tabs.len()
+        self.current_tab = (self.current_tab + 1) % self.tabs.len();
+        self.on_tab_changed()
     }
     pub fn previous(&mut self) {
         if self.current_tab > 0 { self.current_tab -= 1 }
         else { self.current_tab = self.tabs.len() - 1 }
+        self.on_tab_changed()
+    }
+    fn on_tab_changed(&mut self) {
+        let selected = self.paths.paths.get(self.paths.selected_item);
+        if self.current_tab == 3 && self.history.branch_name.as_ref() != selected.map(|path| &path.branch_name) {
+            self.reload_history()
+        }
+    }
+    pub fn reload_history(&mut self) {
+        self.history.load(self.backup.backend(), self.paths.paths.get(self.paths.selected_item))
+    }
+    /// Runs `action` on the selected game and snapshot, then switches to the "Backup" tab to show its logs.
+    pub fn with_selected_snapshot(&mut self, action: fn(&mut Backup, BackupPath, Snapshot)) {
+        let path = match self.paths.paths.get(self.paths.selected_item) { Some(v) => v.clone(), None => return };
+        let snapshot = match self.history.selected() { Some(v) => v.clone(), None => return };
+        if self.backup.uploading || self.backup.downloading || self.backup.previewing { return }
+        action(&mut self.backup, path, snapshot);
+        self.history.branch_name = None;
+        self.current_tab = 2
+    }
+    /// Previews restoring the selected snapshot, only the selected file or folder while browsing it,
+    /// into `destination` or the game folder.
+    pub fn restore_selected(&mut self, destination: Option<PathBuf>) {
+        let path = match self.paths.paths.get(self.paths.selected_item) { Some(v) => v.clone(), None => return };
+        let snapshot = match self.history.selected() { Some(v) => v.clone(), None => return };
+        if self.backup.uploading || self.backup.downloading || self.backup.previewing { return }
+        // Nothing picked yet, e.g. the files are still loading or the snapshot is empty
+        if self.history.browsing() && self.history.selected_file().is_none() { return }
+        let mut restore = Restore::snapshot(path, snapshot);
+        if let Some(file) = self.history.selected_file() { restore = restore.with_file(file.path.clone()) }
+        if let Some(destination) = destination { restore = restore.with_destination(destination) }
+        self.backup.preview_restore(vec![restore]);
+        self.history.close_files();
+        self.history.branch_name = None;
+        self.current_tab = 2
+    }
+    /// Like `restore_selected`, into a folder picked by the user.
+    pub fn restore_selected_elsewhere(&mut self) {
+        if self.history.selected().is_none() { return }
+        if let Some(folder) = rfd::FileDialog::new().set_directory("/").pick_folder() {
+            self.restore_selected(Some(folder))
+        }
     }
     pub fn topbar(&self, f: &mut Frame<impl Backend>, area: Rect) {
         let mut constraints = Vec::new();
@@ -65,8 +125,27 @@ impl App {
             0 => self.menu(f, chunks[1]),
             1 => self.paths.render(f, chunks[1]),
             2 => self.backup.render(f, chunks[1]),
+            3 => self.history.render(f, chunks[1], self.paths.paths.get(self.paths.selected_item)),
             _ => unreachable!()
         }
+        self.render_error(f);
+    }
+    pub fn render_error(&self, f: &mut Frame<impl Backend>) {
+        let error = match &self.error { Some(v) => v, None => return };
+        let area = centered_rect(50, 30, f.size());
+        f.render_widget(Clear, area);
+        f.render_widget(Block::default().title("Error").borders(Borders::ALL).style(Style::default().fg(Color::Red)), area);
+        f.render_widget(
+            Paragraph::new(vec![
+                Spans::from(error.to_string()),
+                Spans::from(""),
+                Spans::from("Press any key to dismiss.")
+            ]).wrap(Wrap { trim: true }),
+            Layout::default()
+                .margin(2)
+                .constraints([Constraint::Min(1)])
+                .split(area)[0]
+        );
     }
     pub fn menu(&self, f: &mut Frame<impl Backend>, area: Rect) {
         let chunks = Layout::default()
@@ -100,4 +179,206 @@ impl App {
             chunks[1]
         );
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use tui::{backend::TestBackend, Terminal};
+
+    use super::App;
+    use crate::{
+        paths::{Paths, BackupPath},
+        backup::Backup,
+        filter::FileFilter,
+        log::Log,
+        diff::Change,
+        storage::Snapshot,
+        restore::SnapshotFile,
+        error::Error
+    };
+
+    /// Renders `app` and compares the text of the frame with "src/snapshots/<name>.txt".
+    /// Run with `UPDATE_SNAPSHOTS=1` to write the snapshots instead, then review their diff.
+    fn assert_frame(name: &str, app: &mut App) {
+        let mut terminal = Terminal::new(TestBackend::new(100, 30)).unwrap();
+        terminal.draw(|f| app.render(f)).unwrap();
+        let buffer = terminal.backend().buffer();
+        let frame: String = (0..buffer.area.height)
+            .map(|y| {
+                let line: String = (0..buffer.area.width).map(|x| buffer.get(x, y).symbol.as_str()).collect();
+                format!("{}\n", line.trim_end())
+            })
+            .collect();
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/snapshots").join(format!("{name}.txt"));
+        if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, frame).unwrap();
+            return
+        }
+        let expected = std::fs::read_to_string(&path)
+            .unwrap_or_else(|_| panic!("No snapshot \"{}\", run the tests with UPDATE_SNAPSHOTS=1 to create it", path.display()));
+        assert!(frame == expected, "\"{name}\" changed, run the tests with UPDATE_SNAPSHOTS=1 to accept it:\n{frame}");
+    }
+
+    /// An app with a few games and a repo URL. No job is started, so nothing is written to its log folder.
+    fn app() -> App {
+        let paths = Paths::new(vec![
+            BackupPath::new("My Game", "/saves/my-game"),
+            BackupPath::new("Other Game", "/saves/other-game")
+                .with_filter(FileFilter::new(Vec::new(), vec!["cache".to_string(), "*.log".to_string()]).unwrap()),
+            BackupPath::new("Third", "/saves/third")
+        ]);
+        let mut backup = Backup::new();
+        backup.repo_url = Some("https://github.com/someone/game-saves".to_string());
+        backup.log_dir = Some(PathBuf::from("/logs"));
+        App::with(paths, backup)
+    }
+
+    fn snapshots() -> Vec<Snapshot> {
+        vec![
+            Snapshot { id: "8f2d0c1e9a".to_string(), date: "2024-03-02T18:30:00+00:00".to_string(), message: "Backup".to_string(), files: 12, size: 40960 },
+            Snapshot { id: "1b7e44a0c3".to_string(), date: "2024-02-20T09:15:00+00:00".to_string(), message: "Backup".to_string(), files: 10, size: 1200 }
+        ]
+    }
+
+    #[test]
+    fn menu() {
+        assert_frame("menu", &mut app());
+    }
+
+    #[test]
+    fn paths() {
+        let mut app = app();
+        app.current_tab = 1;
+        app.paths.selected_item = 1;
+        app.paths.toggle_selected();
+        assert_frame("paths", &mut app);
+    }
+
+    #[test]
+    fn paths_scrolled() {
+        let mut app = app();
+        app.current_tab = 1;
+        app.paths = Paths::new((1..=40).map(|i| BackupPath::new(format!("Game {i}"), format!("/saves/game-{i}"))).collect());
+        app.paths.selected_item = 30;
+        assert_frame("paths_scrolled", &mut app);
+    }
+
+    #[test]
+    fn scrolling_without_games() {
+        let mut app = app();
+        app.paths = Paths::new(Vec::new());
+        app.paths.scroll_down();
+        app.paths.scroll_up();
+        assert_eq!(app.paths.selected_item, 0);
+    }
+
+    #[test]
+    fn add_new_dialog() {
+        let mut app = app();
+        app.current_tab = 1;
+        app.paths.add_new_dialog_folder = Some(PathBuf::from("/saves/new-game"));
+        app.paths.capturing_input = Some("New Ga".to_string());
+        assert_frame("add_new_dialog", &mut app);
+    }
+
+    #[test]
+    fn repo_url_prompt() {
+        let mut app = app();
+        app.current_tab = 2;
+        app.backup.repo_url = None;
+        app.backup.text_input = "https://github.com/some".to_string();
+        assert_frame("repo_url_prompt", &mut app);
+    }
+
+    #[test]
+    fn backup_menu() {
+        let mut app = app();
+        app.current_tab = 2;
+        assert_frame("backup_menu", &mut app);
+    }
+
+    #[test]
+    fn backup_logs() {
+        let mut app = app();
+        app.current_tab = 2;
+        app.backup.uploading = true;
+        app.backup.logs = vec![
+            Log::step("Fetching branch: \"my-game\" ..."),
+            Log::output("Pushed 8f2d0c1e9a"),
+            Log::error("\"Other Game\": Backup folder \"/mnt/nas\" not found"),
+            Log::Summary { succeeded: vec!["My Game".to_string()], failed: vec!["Other Game".to_string()], skipped: Vec::new() },
+            Log::Finished
+        ];
+        assert_frame("backup_logs", &mut app);
+    }
+
+    #[test]
+    fn restore_preview() {
+        let mut app = app();
+        app.current_tab = 2;
+        app.backup.previewing = true;
+        app.backup.pending_restore = Some(Vec::new());
+        let changed = |path: &str, change, newer| Log::FileChanged { game: "My Game".to_string(), path: path.to_string(), change, size: 2048, newer };
+        app.backup.logs = vec![
+            Log::step("Comparing \"My Game\" with the snapshot from 2024-03-02T18:30:00+00:00 ..."),
+            changed("new.sav", Change::Added, false),
+            changed("slot1.sav", Change::Modified, true),
+            changed("slot2.sav", Change::Deleted, false),
+            Log::output("1 to create, 1 to overwrite, 1 to delete, 3 unchanged, 1 local files newer than the snapshot"),
+            Log::Finished
+        ];
+        assert_frame("restore_preview", &mut app);
+    }
+
+    #[test]
+    fn choose_snapshot() {
+        let mut app = app();
+        app.current_tab = 2;
+        app.backup.choosing_snapshot = Some((app.paths.paths[0].clone(), snapshots()));
+        app.backup.text_input = "1".to_string();
+        assert_frame("choose_snapshot", &mut app);
+    }
+
+    #[test]
+    fn history() {
+        let mut app = app();
+        app.current_tab = 3;
+        app.history.snapshots = snapshots();
+        app.history.selected_item = 1;
+        assert_frame("history", &mut app);
+    }
+
+    #[test]
+    fn history_files() {
+        let mut app = app();
+        app.current_tab = 3;
+        app.history.snapshots = snapshots();
+        app.history.files = Some(vec![
+            SnapshotFile { path: "profiles".to_string(), is_dir: true, size: 0 },
+            SnapshotFile { path: "profiles/player.dat".to_string(), is_dir: false, size: 512 },
+            SnapshotFile { path: "slot1.sav".to_string(), is_dir: false, size: 40448 }
+        ]);
+        app.history.selected_file = 1;
+        assert_frame("history_files", &mut app);
+    }
+
+    #[test]
+    fn restore_needs_a_selected_file_while_browsing() {
+        let mut app = app();
+        app.current_tab = 3;
+        app.history.snapshots = snapshots();
+        app.history.files = Some(Vec::new());
+        app.restore_selected(None);
+        assert!(app.backup.pending_restore.is_none() && !app.backup.previewing);
+        assert_eq!(app.current_tab, 3);
+    }
+
+    #[test]
+    fn error_popup() {
+        let mut app = app();
+        app.error = Some(Error::BackupFolderNotFound(PathBuf::from("/mnt/nas/game-saves")));
+        assert_frame("error_popup", &mut app);
+    }
+}
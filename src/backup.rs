@@ -3,64 +3,174 @@ use std::iter::Iterator;
 use walkdir::WalkDir;
 use zip::write::FileOptions;
 
-use std::{path::{Path, PathBuf}, process::Command, env::set_current_dir, ffi::OsStr, sync::{mpsc::{Receiver, Sender, channel}},
-    io::Write, fs::File};
+use std::{path::{Path, PathBuf}, sync::{Arc, mpsc::{Receiver, channel}}, io::Write, fs::File, collections::HashMap, any::Any, panic::AssertUnwindSafe};
 
-const PATH: &'static str = "./conf.txt";
+const FILE: &str = "conf.txt";
+const LOG_FILE: &str = "uploading.log";
+const JSON_LOG_FILE: &str = "uploading.jsonl";
 
-use chrono::Utc;
-use dirs::document_dir;
-use fs_extra::dir::CopyOptions;
-use tui::{Frame, backend::Backend, layout::{Rect, Constraint, Layout}, widgets::Paragraph, text::{Spans, Span}, style::{Style, Color}};
+use tui::{Frame, backend::Backend, layout::{Rect, Constraint, Layout}, widgets::Paragraph, text::{Spans, Span}, style::{Style, Color, Modifier}};
 
-use crate::paths::BackupPath;
+use crate::{
+    paths::{BackupPath, format_path_to_absolute, format_path_to_relative},
+    log::{Log, LogSender, Summary, send},
+    storage::{StorageBackend, GitBackend, LocalBackend, DedupBackend, S3Backend, S3Config, Snapshot, find_snapshot},
+    error::Error,
+    diff::{Change, FolderDiff, diff_folders},
+    util::format_size,
+    restore::{Restore, SafetySnapshots, run_restore, run_restore_preview, run_rollback, fetch_to_temp},
+    locations::{config_dir, config_file, cache_dir}
+};
+
+/// Where the snapshots go, set with "backend" in "conf.txt".
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BackendKind {
+    Git,
+    Local,
+    Dedup,
+    S3
+}
+impl BackendKind {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "git" => Some(Self::Git),
+            "local" => Some(Self::Local),
+            "dedup" => Some(Self::Dedup),
+            "s3" => Some(Self::S3),
+            _ => None
+        }
+    }
+    /// The name written in "conf.txt".
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Git => "git",
+            Self::Local => "local",
+            Self::Dedup => "dedup",
+            Self::S3 => "s3"
+        }
+    }
+    /// Shown before the target in the menu.
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Git => "Repo URL: ",
+            Self::Local | Self::Dedup => "Backup folder: ",
+            Self::S3 => "Bucket: "
+        }
+    }
+    /// What to enter while no target is set, and an example of it.
+    pub fn prompt(self) -> (&'static str, &'static str) {
+        match self {
+            Self::Git => ("Please enter the repo url, then press \"Enter\" to continue.", "Ex: https://github.com/muriel-guedes/game-saves"),
+            Self::Local | Self::Dedup => ("Please enter the backup folder, then press \"Enter\" to continue.", "Ex: /mnt/nas/game-saves"),
+            Self::S3 => (
+                "Please set s3_endpoint, s3_bucket, s3_access_key and s3_secret_key (and optionally s3_region) in \"conf.txt\".",
+                "Ex: s3_endpoint = http://localhost:9000"
+            )
+        }
+    }
+    /// Where the backups of `backup` go, the repo url, the local folder or the bucket, if it's set.
+    pub fn target(self, backup: &Backup) -> Option<String> {
+        match self {
+            Self::Git => backup.repo_url.clone(),
+            Self::Local | Self::Dedup => backup.local_root.as_ref().map(|root| root.display().to_string()),
+            Self::S3 => backup.s3.as_ref().map(|s3| format!("{}/{}", s3.endpoint, s3.bucket))
+        }
+    }
+}
 
 pub struct Backup {
     pub text_input: String,
+    pub backend_kind: BackendKind,
     pub repo_url: Option<String>,
+    pub local_root: Option<PathBuf>,
+    pub s3: Option<S3Config>,
+    /// Committer of the git snapshots.
+    pub git_user_name: Option<String>,
+    pub git_user_email: Option<String>,
     pub uploading: bool,
     pub downloading: bool,
-    pub receive_log: Option<Receiver<Option<String>>>,
-    pub logs: Vec<String>
+    /// Showing what a backup would change.
+    pub previewing: bool,
+    pub receive_log: Option<Receiver<Log>>,
+    pub logs: Vec<Log>,
+    pub choosing_snapshot: Option<(BackupPath, Vec<Snapshot>)>,
+    /// Shown in a preview, waiting for the user to confirm.
+    pub pending_restore: Option<Vec<Restore>>,
+    /// Where "uploading.log" and "uploading.jsonl" are written, `None` if there's no home folder to put them in.
+    pub log_dir: Option<PathBuf>,
+    /// The log files of the running job, the logs are still shown if they can't be written.
+    log_file: Option<File>,
+    json_log_file: Option<File>
 }
 impl Backup {
     pub fn new() -> Self {
-        let mut repo_url = None;
-        if Path::new(PATH).exists() {
-            let content = std::fs::read_to_string(PATH).unwrap();
-            for line in content.lines() {
-                if line.len() == 0 { continue }
-                let mut line = line.split('=');
-                let name = line.next().unwrap().trim();
-                if name == "repo_url" {
-                    let value = line.next().unwrap().trim().to_string();
-                    if value.len() == 0 { continue }
-                    repo_url = Some(value);
-                }
-            }
-        }
         Self {
             text_input: String::new(),
-            repo_url,
+            backend_kind: BackendKind::Git,
+            repo_url: None,
+            local_root: None,
+            s3: None,
+            git_user_name: None,
+            git_user_email: None,
             uploading: false,
             downloading: false,
+            previewing: false,
             receive_log: None,
-            logs: Vec::new()
+            logs: Vec::new(),
+            choosing_snapshot: None,
+            pending_restore: None,
+            log_dir: cache_dir().ok(),
+            log_file: None,
+            json_log_file: None
         }
     }
+    /// Reads the settings from "conf.txt", a missing file means nothing is configured yet.
+    pub fn read() -> Result<Self, Error> {
+        let mut backup = Self::new();
+        let config = config_file(FILE)?;
+        if !config.exists() { return Ok(backup) }
+        let mut s3_settings = HashMap::new();
+        for (i, line) in std::fs::read_to_string(&config)?.lines().enumerate() {
+            if line.is_empty() { continue }
+            let (name, value) = line.split_once('=').unwrap_or((line, ""));
+            let (name, value) = (name.trim(), value.trim().to_string());
+            if value.is_empty() { continue }
+            match name {
+                "backend" => backup.backend_kind = match BackendKind::parse(&value) {
+                    Some(v) => v,
+                    None => return Err(Error::Config {
+                        file: config.display().to_string(),
+                        line: i + 1,
+                        message: format!("unknown backend \"{value}\", expected git, local, dedup or s3")
+                    })
+                },
+                "repo_url" => backup.repo_url = Some(value),
+                "local_root" => backup.local_root = Some(format_path_to_absolute(value)),
+                "git_user_name" => backup.git_user_name = Some(value),
+                "git_user_email" => backup.git_user_email = Some(value),
+                name if name.starts_with("s3_") => { s3_settings.insert(name.to_string(), value); },
+                _ => {}
+            }
+        }
+        backup.s3 = s3_config(s3_settings);
+        Ok(backup)
+    }
     pub fn render(&mut self, f: &mut Frame<impl Backend>, area: Rect) {
-        if self.uploading || self.downloading {
+        if self.uploading || self.downloading || self.previewing {
             self.render_logs(f, area)
-        } else if let Some(repo_url) = self.repo_url.clone() {
-            self.render_menu(f, area, repo_url)
+        } else if self.choosing_snapshot.is_some() {
+            self.render_choose_snapshot(f, area)
+        } else if let Some(target) = self.target() {
+            self.render_menu(f, area, target)
         }else {
             self.render_enter_repo_url(f, area)
         }
     }
     pub fn render_enter_repo_url(&self, f: &mut Frame<impl Backend>, area: Rect) {
+        let (prompt, example) = self.backend_kind.prompt();
         f.render_widget(Paragraph::new(vec![
-            Spans::from("Please enter the repo url, then press \"Enter\" to continue."),
-            Spans::from("Ex: https://github.com/muriel-guedes/game-saves"),
+            Spans::from(prompt),
+            Spans::from(example),
             Spans::from(""),
             Spans::from(vec![
                 Span::raw("> "),
@@ -71,42 +181,86 @@ impl Backup {
             .constraints([Constraint::Min(1)])
             .split(area)[0]);
     }
-    pub fn render_menu(&self, f: &mut Frame<impl Backend>, area: Rect, repo_url: String) {
+    pub fn render_menu(&self, f: &mut Frame<impl Backend>, area: Rect, target: String) {
         f.render_widget(Paragraph::new(vec![
             Spans::from(vec![
-                Span::raw("Repo URL: "),
-                Span::from(repo_url.clone())
+                Span::raw(self.backend_kind.label()),
+                Span::from(target)
             ]),
-            Spans::from("Press \"Enter\" to backup all your data, \"T\" to compress all data, or \"R\" to restore."),
+            Spans::from("Press \"Enter\" to backup, \"T\" to compress all data, or \"R\" to restore."),
+            Spans::from("Only the games ticked in the \"Paths\" tab are backed up or restored, all of them if none is."),
+            Spans::from("Press \"V\" to restore a specific snapshot of the selected game."),
+            Spans::from("Press \"P\" to preview what a backup would change, without storing anything."),
+            Spans::from("Press \"U\" to undo the last restore, putting back the files it replaced."),
         ]), Layout::default()
             .margin(2)
             .constraints([Constraint::Min(1)])
             .split(area)[0]);
     }
+    pub fn render_choose_snapshot(&self, f: &mut Frame<impl Backend>, area: Rect) {
+        let (path, snapshots) = match &self.choosing_snapshot { Some(v) => v, None => return };
+        let mut spans = vec![
+            Spans::from(format!("Snapshots of \"{}\":", path.name)),
+            Spans::from("")
+        ];
+        for (i, snapshot) in snapshots.iter().enumerate() {
+            spans.push(Spans::from(format!(
+                "{i:>3}  {}  {}  {}", snapshot.date, &snapshot.id[..8.min(snapshot.id.len())], snapshot.message
+            )));
+        }
+        spans.push(Spans::from(""));
+        spans.push(Spans::from("Type the index, hash or date of the snapshot to restore."));
+        spans.push(Spans::from("Press \"Enter\" to restore it or \"Esc\" to cancel."));
+        spans.push(Spans::from(vec![
+            Span::raw("> "),
+            Span::from(self.text_input.clone())
+        ]));
+        f.render_widget(Paragraph::new(spans), Layout::default()
+            .margin(2)
+            .constraints([Constraint::Min(1)])
+            .split(area)[0]);
+    }
     pub fn render_logs(&mut self, f: &mut Frame<impl Backend>, area: Rect) {
         if let Some(rx) = self.receive_log.as_ref() {
-            if let Some(log) = rx.recv().unwrap() {
-                self.logs.push(log)
-            } else {
-                self.receive_log = None
-            }
+            let log = match rx.recv() {
+                Ok(Log::Finished) | Err(_) => {
+                    self.receive_log = None;
+                    Log::Finished
+                },
+                Ok(log) => log
+            };
+            self.write_log(&log);
+            self.logs.push(log);
         }
         let mut spans = Vec::new();
-        let mut log_file = std::fs::OpenOptions::new()
-            .create(true)
-            .truncate(true)
-            .write(true)
-            .append(false)
-            .open(get_uploading_log_path()).unwrap();
         for log in &self.logs {
-            if log.len() == 0 { continue }
-            let mut log = log.clone();
-            let color = if log.starts_with('#') {
-                log.remove(0);
-                Color::Yellow
-            } else { Color::Reset };
-            spans.push(Spans::from(Span::styled(log.clone(), Style::default().fg(color))));
-            writeln!(log_file, "{log}").unwrap();
+            let text = log.to_string();
+            if text.trim().is_empty() { continue }
+            let (text, color) = match log {
+                Log::FileCopied { .. } => continue,
+                Log::Step { .. } => (text, Color::Yellow),
+                Log::FileChanged { newer: true, .. } => {
+                    spans.push(Spans::from(Span::styled(text, Style::default().fg(Color::Red).add_modifier(Modifier::BOLD))));
+                    continue
+                },
+                Log::FileChanged { change, .. } => (text, match change {
+                    Change::Added => Color::Green,
+                    Change::Modified => Color::Cyan,
+                    Change::Deleted => Color::LightRed
+                }),
+                Log::Error { .. } => (text, Color::Red),
+                Log::Summary { .. } => {
+                    spans.extend(text.lines().map(|line| Spans::from(Span::styled(line.to_string(), Style::default().fg(Color::Cyan)))));
+                    continue
+                },
+                Log::Finished if self.pending_restore.is_some() => (
+                    "Press \"Y\" to restore, replacing the local files as listed above, or any other key to cancel.".to_string(),
+                    Color::Yellow
+                ),
+                Log::Finished => ("Finished, press \"Enter\" to continue.".to_string(), Color::Yellow),
+                Log::Output { .. } => (text, Color::Reset)
+            };
+            spans.push(Spans::from(Span::styled(text, Style::default().fg(color))));
         }
         let chunks = Layout::default()
             .margin(0)
@@ -115,184 +269,325 @@ impl Backup {
                 Constraint::Percentage(100)
             ])
             .split(area);
-        f.render_widget(Paragraph::new(
-            format!("You can found full logs in: {}", get_uploading_log_path().display())
-        ), chunks[0]);
+        let text = match &self.log_dir {
+            Some(dir) => format!("You can found full logs in: {}", dir.join(LOG_FILE).display()),
+            None => "The logs aren't written to a file, no home folder was found.".to_string()
+        };
+        f.render_widget(Paragraph::new(text), chunks[0]);
         f.render_widget(Paragraph::new(spans), chunks[1]);
     }
-    pub fn set_repo_url(&mut self) {
-        if self.text_input.len() == 0 { return }
-        self.repo_url = Some(self.text_input.clone());
-        self.text_input = String::new();
-        std::fs::write(PATH, format!("repo_url = {}", self.repo_url.as_ref().unwrap())).unwrap();
+    /// Appends `log` to the log files of the running job, closing them once it's finished.
+    fn write_log(&mut self, log: &Log) {
+        if let Some(file) = self.json_log_file.as_mut() { writeln!(file, "{}", log.to_json()).ok(); }
+        let text = log.to_string();
+        if let (false, Some(file)) = (text.trim().is_empty(), self.log_file.as_mut()) { writeln!(file, "{text}").ok(); }
+        if matches!(log, Log::Finished) {
+            self.log_file = None;
+            self.json_log_file = None
+        }
     }
-    pub fn backup(&mut self, paths: Vec<BackupPath>) {
-        self.uploading = true;
-        self.logs.clear();
-        let (tx, rx): (Sender<Option<String>>, Receiver<Option<String>>) = channel();
-        self.receive_log = Some(rx);
-        let repo_url = self.repo_url.as_ref().unwrap().clone();
-        std::thread::spawn(move || {
-            send(&tx, "#Creating temp folder ...");
-            std::fs::remove_dir_all("./temp").ok();
-            std::fs::create_dir("./temp").unwrap();
-            set_current_dir("./temp").unwrap();
-            send(&tx, "#Initializing repo ...");
-            run_command(&tx, "git", ["init"]);
-            send(&tx, "#Adding origin ...");
-            run_command(&tx, "git", ["remote", "add", "origin", &repo_url]);
-            run_command(&tx, "git", ["fetch"]);
-            run_command(&tx, "git", ["checkout", "--orphan", "master"]);
-            run_command(&tx, "git", ["pull", "origin", "master", "-f"]);
-
-            // Update repo README.md
-            let readme_content = std::fs::read_to_string("README.md").unwrap_or_default();
-            let readme_content_lines: Vec<&str> = readme_content.lines().collect();
-            let mut readme = std::fs::OpenOptions::new()
-                .truncate(false)
-                .create(true)
-                .write(true)
-                .append(true)
-                .open("README.md").unwrap();
-            for readme_content_line in &readme_content_lines {
-                writeln!(readme, "{}", readme_content_line).unwrap();
-            }
-            'p: for path in &paths {
-                let line = format!("{} = {}<br>", path.name, path.relative_path.display());
-                for readme_content_line in &readme_content_lines {
-                    if **readme_content_line == *line.as_str() {
-                        continue 'p
-                    }
+    pub fn set_repo_url(&mut self) -> Result<(), Error> {
+        if self.text_input.is_empty() { return Ok(()) }
+        let value = std::mem::take(&mut self.text_input);
+        match self.backend_kind {
+            BackendKind::Git => self.repo_url = Some(value),
+            BackendKind::Local | BackendKind::Dedup => self.local_root = Some(format_path_to_absolute(value)),
+            BackendKind::S3 => return Ok(())
+        }
+        self.save_config()
+    }
+    /// Writes the settings editable from the TUI, keeping every other line of "conf.txt".
+    fn save_config(&self) -> Result<(), Error> {
+        let mut content = String::new();
+        let config = config_file(FILE)?;
+        for line in std::fs::read_to_string(&config).unwrap_or_default().lines() {
+            let name = line.split('=').next().unwrap_or_default().trim();
+            if line.trim().is_empty() || ["backend", "repo_url", "local_root"].contains(&name) { continue }
+            content += &format!("{line}\r\n");
+        }
+        content += &format!("backend = {}\r\n", self.backend_kind.name());
+        if let Some(repo_url) = &self.repo_url {
+            content += &format!("repo_url = {repo_url}\r\n");
+        }
+        if let Some(local_root) = &self.local_root {
+            content += &format!("local_root = {}\r\n", format_path_to_relative(local_root).display());
+        }
+        std::fs::create_dir_all(config_dir()?)?;
+        std::fs::write(config, content)?;
+        Ok(())
+    }
+    /// Where the backups go, if it's set.
+    pub fn target(&self) -> Option<String> {
+        self.backend_kind.target(self)
+    }
+    /// The storage backend configured in "conf.txt", if any.
+    pub fn backend(&self) -> Option<Arc<dyn StorageBackend>> {
+        match self.backend_kind {
+            BackendKind::Local => Some(Arc::new(LocalBackend::new(self.local_root.clone()?))),
+            BackendKind::Dedup => Some(Arc::new(DedupBackend::new(self.local_root.clone()?))),
+            BackendKind::S3 => Some(Arc::new(S3Backend::new(self.s3.clone()?))),
+            BackendKind::Git => {
+                let mut backend = GitBackend::new(self.repo_url.clone()?);
+                if let (Some(name), Some(email)) = (&self.git_user_name, &self.git_user_email) {
+                    backend = backend.with_identity(name, email);
                 }
-                writeln!(readme, "{line}").unwrap();
+                Some(Arc::new(backend))
             }
-            run_command(&tx, "git", ["add", "."]);
-            run_command(&tx, "git", ["commit", "-m", &format!("\"{}\"", Utc::now())]);
-            run_command(&tx, "git", ["push", "origin", "master", "-f"]);
-            
-            for path in &paths {
-                if !path.absolute_path.exists() {
-                    send(&tx, format!("#Skiping unexisting path: \"{}\" ...", path.absolute_path.display()));
-                    continue
-                }
-                
-                send(&tx, format!("#Switching to branch: \"{}\" ...", path.branch_name));
-                run_command(&tx, "git", ["checkout", "--orphan", &path.branch_name]);
-
-                send(&tx, format!("#Copying files from \"{}\" to \"./temp/content\" ...", path.absolute_path.display()));
-                copy_folder_files_to_folder(&path.absolute_path, "./content");
-
-                std::fs::write("README.md", &format!("{}", path.absolute_path.display())).unwrap();
-                
-                send(&tx, "#Pushing to branch ...");
-                run_command(&tx, "git", ["add", "."]);
-                run_command(&tx, "git", ["commit", "-m", &format!("\"{}\"", Utc::now())]);
-                run_command(&tx, "git", ["push", "origin", &path.branch_name, "-f"]);
+        }
+    }
+    /// Starts `job` on a background thread, showing its logs.
+    fn spawn(&mut self, job: impl FnOnce(&LogSender, &dyn StorageBackend) + Send + 'static) {
+        let backend = match self.backend() {
+            Some(v) => v,
+            None => {
+                self.uploading = false;
+                self.downloading = false;
+                self.previewing = false;
+                self.pending_restore = None;
+                return
             }
-            set_current_dir("../").unwrap();
-            send(&tx, "#Finished, press \"Enter\" to continue.");
-            tx.send(None).unwrap();
-            std::fs::remove_dir_all("./temp").ok();
-        });
+        };
+        self.spawn_local(move |tx| job(tx, backend.as_ref()));
     }
-    pub fn restore(&mut self, paths: Vec<BackupPath>) {
-        self.downloading = true;
+    /// Starts `job`, that doesn't need the backend, on a background thread, showing its logs.
+    fn spawn_local(&mut self, job: impl FnOnce(&LogSender) + Send + 'static) {
         self.logs.clear();
-        let (tx, rx): (Sender<Option<String>>, Receiver<Option<String>>) = channel();
+        if let Some(dir) = &self.log_dir {
+            std::fs::create_dir_all(dir).ok();
+            self.log_file = File::create(dir.join(LOG_FILE)).ok();
+            self.json_log_file = File::create(dir.join(JSON_LOG_FILE)).ok();
+        }
+        let (tx, rx): (LogSender, Receiver<Log>) = channel();
         self.receive_log = Some(rx);
-        let repo_url = self.repo_url.as_ref().unwrap().clone();
         std::thread::spawn(move || {
-            send(&tx, "#Creating temp folder ...");
-            std::fs::remove_dir_all("./temp").ok();
-            std::fs::create_dir("./temp").unwrap();
-            set_current_dir("./temp").unwrap();
-            send(&tx, "#Initializing repo ...");
-            run_command(&tx, "git", ["init"]);
-            run_command(&tx, "git", ["remote", "add", "origin", &repo_url]);
+            let result = std::panic::catch_unwind(AssertUnwindSafe(|| job(&tx)));
+            if let Err(panic) = result { send(&tx, Log::error(format!("Crashed: {}", panic_message(panic.as_ref())))) }
+            send(&tx, Log::Finished);
+        });
+    }
+    pub fn backup(&mut self, paths: Vec<BackupPath>) {
+        self.uploading = true;
+        self.spawn(move |tx, backend| run_backup(tx, backend, &paths));
+    }
+    /// Previews restoring the newest snapshot of every path, see `preview_restore`.
+    pub fn restore(&mut self, paths: Vec<BackupPath>) {
+        self.preview_restore(paths.into_iter().map(Restore::latest).collect())
+    }
+    /// Shows what `restores` would do to the local files, they're only restored once `confirm_restore` is called.
+    pub fn preview_restore(&mut self, restores: Vec<Restore>) {
+        self.previewing = true;
+        self.pending_restore = Some(restores.clone());
+        self.spawn(move |tx, backend| { run_restore_preview(tx, backend, &restores); });
+    }
+    pub fn confirm_restore(&mut self) {
+        let restores = match self.pending_restore.take() { Some(v) => v, None => return };
+        self.previewing = false;
+        self.downloading = true;
+        self.spawn(move |tx, backend| match SafetySnapshots::new() {
+            Ok(safety) => run_restore(tx, backend, &safety, &restores),
+            Err(e) => send(tx, Log::error(e.to_string()))
+        });
+    }
+    /// Puts back the local files the last restore replaced.
+    pub fn rollback(&mut self, paths: Vec<BackupPath>) {
+        self.downloading = true;
+        self.spawn_local(move |tx| match SafetySnapshots::new() {
+            Ok(safety) => run_rollback(tx, &safety, &paths),
+            Err(e) => send(tx, Log::error(e.to_string()))
+        });
+    }
+    pub fn cancel_restore(&mut self) {
+        self.pending_restore = None;
+        self.previewing = false;
+    }
+    /// Shows what `backup` would store, without storing anything.
+    pub fn preview(&mut self, paths: Vec<BackupPath>) {
+        self.previewing = true;
+        self.spawn(move |tx, backend| run_diff(tx, backend, &paths));
+    }
+    /// Lists every snapshot stored for `path`, newest first.
+    pub fn list_snapshots(&self, path: &BackupPath) -> Result<Vec<Snapshot>, Error> {
+        match self.backend() {
+            Some(backend) => backend.list_snapshots(path),
+            None => Ok(Vec::new())
+        }
+    }
+    pub fn dialog_restore_snapshot(&mut self, path: BackupPath) -> Result<(), Error> {
+        let snapshots = self.list_snapshots(&path)?;
+        self.text_input = String::new();
+        self.choosing_snapshot = Some((path, snapshots));
+        Ok(())
+    }
+    pub fn confirm_restore_snapshot(&mut self) {
+        let (path, snapshots) = match self.choosing_snapshot.take() { Some(v) => v, None => return };
+        let query = std::mem::take(&mut self.text_input);
+        match find_snapshot(&snapshots, &query).cloned() {
+            Some(snapshot) => self.restore_snapshot(path, snapshot),
+            None => self.choosing_snapshot = Some((path, snapshots))
+        }
+    }
+    /// Previews restoring the files of a single snapshot into `path.absolute_path`.
+    pub fn restore_snapshot(&mut self, path: BackupPath, snapshot: Snapshot) {
+        self.preview_restore(vec![Restore::snapshot(path, snapshot)])
+    }
+    /// Removes a single snapshot, keeping the others.
+    pub fn delete_snapshot(&mut self, path: BackupPath, snapshot: Snapshot) {
+        self.uploading = true;
+        self.spawn(move |tx, backend| if let Err(e) = backend.delete_snapshot(tx, &path, &snapshot) {
+            send(tx, Log::error(e.to_string()))
+        });
+    }
+    /// Zips the files of every path into "saves.zip", showing its logs.
+    pub fn compress(&mut self, paths: Vec<BackupPath>) {
+        self.uploading = true;
+        self.spawn_local(move |tx| run_compress(tx, &paths));
+    }
+}
 
-            for path in paths {
-                send(&tx, format!("#Downloading branch \"{}\" ...", path.branch_name));
-                run_command(&tx, "git", ["checkout", &path.branch_name]);
-                run_command(&tx, "git", ["pull", "origin", &path.branch_name, "--force"]);
-                send(&tx, format!("#Copying to \"{}\" ...", path.absolute_path.display()));
-                copy_folder_files_to_folder(&path.absolute_path, "./content");
-            }
+/// Zips the files of every path into "saves.zip" in the current directory, blocking until done.
+pub fn run_compress(tx: &LogSender, paths: &[BackupPath]) {
+    if let Err(e) = write_zip(tx, paths) { send(tx, Log::error(e.to_string())) }
+}
 
-            set_current_dir("../").unwrap();
-            std::fs::remove_dir_all("./temp").ok();
-            send(&tx, "#Finished, press \"Enter\" to continue.");
-            tx.send(None).unwrap();
-        });
+fn write_zip(tx: &LogSender, paths: &[BackupPath]) -> Result<(), Error> {
+    let file = File::create("saves.zip")?;
+    let mut zip = zip::ZipWriter::new(file);
+    let options = FileOptions::default()
+        .compression_method(zip::CompressionMethod::Zstd)
+        .compression_level(Some(22))
+        .unix_permissions(0o755);
+
+    for path in paths {
+        if path.absolute_path.exists() {
+            let mut buffer = Vec::new();
+            for (_, path) in path.files()? {
+                send(tx, Log::step(format!("Adding \"{}\" ...", path.display())));
+                zip.start_file(path.to_string_lossy(), options)?;
+                let mut f = File::open(&path)?;
+                f.read_to_end(&mut buffer)?;
+                zip.write_all(&buffer)?;
+                buffer.clear();
+            }
+        }else {
+            send(tx, Log::step(format!("\"{}\" not found, skipping it", path.absolute_path.display())));
+        }
     }
-    pub fn compress(&self, paths: Vec<BackupPath>) {
-        println!("");
 
-        let file = File::create(&"saves.zip").unwrap();
-        let mut zip = zip::ZipWriter::new(file);
-        let options = FileOptions::default()
-            .compression_method(zip::CompressionMethod::Zstd)
-            .compression_level(Some(22))
-            .unix_permissions(0o755);
+    zip.finish()?;
+    Ok(())
+}
 
+/// Stores a new snapshot of every path, blocking until done.
+///
+/// A game whose snapshot fails is reported and skipped, if the backend can't even be prepared nothing is stored.
+pub fn run_backup(tx: &LogSender, backend: &dyn StorageBackend, paths: &[BackupPath]) {
+    let mut summary = Summary::default();
+    if let Err(e) = backend.prepare(tx, paths) {
+        send(tx, Log::error(e.to_string()));
+        send(tx, Log::step("Backup aborted."));
+        for path in paths { summary.skip(&path.name) }
+    } else {
         for path in paths {
-            if path.absolute_path.exists() {
-                let path = path.absolute_path;
-                let mut buffer = Vec::new();
-                for entry in WalkDir::new(path) {
-                    let entry = entry.unwrap();
-                    let path = entry.path();
-                    if path.is_file() {
-                        println!("adding file {:?}", path);
-                        zip.start_file(path.to_string_lossy(), options).unwrap();
-                        let mut f = File::open(path).unwrap();
-                        f.read_to_end(&mut buffer).unwrap();
-                        zip.write_all(&*buffer).unwrap();
-                        buffer.clear();
-                    }
-                }
-            }else {
-                eprintln!("\"{}\" not found", path.absolute_path.display());
+            if skip_missing(tx, &mut summary, path) { continue }
+            match backend.put_snapshot(tx, path) {
+                Ok(()) => summary.succeed(&path.name),
+                Err(e) => summary.fail(tx, &path.name, e)
             }
         }
+    }
+    summary.send(tx);
+}
 
-        zip.finish().unwrap();
-        println!("Finished.");
+/// Skips `path` if its folder doesn't exist, returning whether it did.
+fn skip_missing(tx: &LogSender, summary: &mut Summary, path: &BackupPath) -> bool {
+    if path.absolute_path.exists() { return false }
+    send(tx, Log::step(format!("Skipping missing path: \"{}\" ...", path.absolute_path.display())));
+    summary.skip(&path.name);
+    true
+}
+
+/// Compares every path with its newest snapshot without storing anything, blocking until done.
+pub fn run_diff(tx: &LogSender, backend: &dyn StorageBackend, paths: &[BackupPath]) {
+    let mut summary = Summary::default();
+    for path in paths {
+        if skip_missing(tx, &mut summary, path) { continue }
+        send(tx, Log::step(format!("Comparing \"{}\" with its latest snapshot ...", path.name)));
+        let diff = match diff_with_latest(backend, path) {
+            Ok(v) => v,
+            Err(e) => {
+                summary.fail(tx, &path.name, e);
+                continue
+            }
+        };
+        let (mut counts, mut bytes) = ([0; 3], [0; 3]);
+        for file in diff.changes {
+            let i = file.change as usize;
+            counts[i] += 1;
+            bytes[i] += file.size;
+            send(tx, Log::FileChanged { game: path.name.clone(), path: file.path, change: file.change, size: file.size, newer: false });
+        }
+        send(tx, Log::output(format!(
+            "{} new ({}), {} modified ({}), {} deleted ({}), {} unchanged",
+            counts[0], format_size(bytes[0]), counts[1], format_size(bytes[1]), counts[2], format_size(bytes[2]), diff.unchanged
+        )));
+        summary.succeed(&path.name);
     }
+    summary.send(tx);
 }
 
-fn send(tx: &Sender<Option<String>>, msg: impl AsRef<str>) {
-    tx.send(Some(msg.as_ref().to_string())).unwrap();
+/// Compares the current files of `path` with its newest snapshot.
+fn diff_with_latest(backend: &dyn StorageBackend, path: &BackupPath) -> Result<FolderDiff, Error> {
+    let snapshot = backend.list_snapshots(path)?.into_iter().next();
+    let (_temp, stored) = fetch_to_temp(backend, path, snapshot.as_ref())?;
+    diff_folders(&stored, &path.absolute_path, &path.filter)
 }
-fn run_command(
-    tx: &Sender<Option<String>>,
-    command: impl AsRef<OsStr>,
-    args: impl IntoIterator<Item = impl AsRef<OsStr>>
-) {
-    let res = Command::new(command).args(args).output().unwrap();
-    if res.status.success() {
-        tx.send(Some(
-            String::from_utf8_lossy(&res.stdout).into()
-        )).unwrap()
-    } else {
-        tx.send(Some(
-            format!("Error: {}", String::from_utf8_lossy(&res.stderr))
-        )).unwrap()
+
+/// Builds the S3 settings from the "s3_*" keys of "conf.txt", if every required one is set.
+fn s3_config(mut settings: HashMap<String, String>) -> Option<S3Config> {
+    Some(S3Config {
+        endpoint: settings.remove("s3_endpoint")?,
+        bucket: settings.remove("s3_bucket")?,
+        region: settings.remove("s3_region").unwrap_or_else(|| "us-east-1".to_string()),
+        access_key: settings.remove("s3_access_key")?,
+        secret_key: settings.remove("s3_secret_key")?
+    })
+}
+
+/// The text a thread panicked with.
+pub fn panic_message(panic: &(dyn Any + Send)) -> String {
+    match panic.downcast_ref::<&str>() {
+        Some(message) => message.to_string(),
+        None => panic.downcast_ref::<String>().cloned().unwrap_or_default()
     }
 }
 
-fn get_uploading_log_path() -> PathBuf {
-    document_dir().unwrap().join("uploading.log")
+/// Like `copy_folder_files_to_folder`, with only `files`, e.g. the ones of a game that its filter keeps.
+pub fn copy_files_to_folder(tx: &LogSender, files: Vec<(String, PathBuf)>, to: impl AsRef<Path>) -> Result<(), Error> {
+    let to = to.as_ref();
+    std::fs::remove_dir_all(to).ok();
+    std::fs::create_dir(to)?;
+    for (relative, file) in files {
+        let target = to.join(&relative);
+        if let Some(parent) = target.parent() { std::fs::create_dir_all(parent)? }
+        let size = std::fs::copy(&file, &target)?;
+        send(tx, Log::FileCopied { from: file, to: target, size });
+    }
+    Ok(())
 }
 
-fn copy_folder_files_to_folder(from: impl AsRef<Path>, to: impl AsRef<Path>) {
-    std::fs::remove_dir_all(to.as_ref()).ok();
-    std::fs::create_dir(to.as_ref()).unwrap();
-    let dir = match std::fs::read_dir(&from) { Ok(v) => v, Err(_) => return };
-    for path in dir {
-        let path = match path { Ok(v) => v, Err(_) => return };
-        fs_extra::copy_items(&vec![path.path()], to.as_ref(), &CopyOptions {
-            overwrite: true,
-            ..Default::default()
-        }).unwrap();
+pub fn copy_folder_files_to_folder(tx: &LogSender, from: impl AsRef<Path>, to: impl AsRef<Path>) -> Result<(), Error> {
+    let (from, to) = (from.as_ref(), to.as_ref());
+    std::fs::remove_dir_all(to).ok();
+    std::fs::create_dir(to)?;
+    for entry in WalkDir::new(from).min_depth(1) {
+        let entry = entry?;
+        let target = to.join(entry.path().strip_prefix(from).unwrap());
+        if entry.file_type().is_dir() {
+            std::fs::create_dir_all(&target)?;
+        } else {
+            let size = std::fs::copy(entry.path(), &target)?;
+            send(tx, Log::FileCopied { from: entry.path().to_path_buf(), to: target, size });
+        }
     }
+    Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,427 @@
+use std::{path::PathBuf, sync::{Arc, mpsc::channel, atomic::{AtomicBool, Ordering}}, io::Write};
+
+use crate::{paths::{Paths, BackupPath, validate_name}, backup::{Backup, run_backup, run_diff, run_compress}, restore::{Restore, SafetySnapshots, run_restore, run_restore_preview, run_rollback, list_snapshot_files}, util::format_size, filter::FileFilter, log::{Log, LogSender, send}, storage::{StorageBackend, Snapshot, find_snapshot}};
+
+const USAGE: &str = "Usage: save-saver [--json] [--config DIR] [COMMAND]
+
+Without a command the interactive interface is started.
+With \"--json\" events are printed as JSON lines instead of text.
+With \"--config\" \"paths.txt\" and \"conf.txt\" are read from DIR instead of ~/.config/save-saver.
+
+Commands:
+  backup [--game NAME]...  Backup all games, or only the given ones
+  diff [--game NAME]...    Show what a backup would change, without storing anything
+  restore [--game NAME]... [--snapshot ID|INDEX|DATE] [--file PATH] [--to DIR] [--dry-run] [--yes]
+                           Restore all games from the repo, or only the given ones, showing what
+                           would be replaced and asking first if any local file would be overwritten or deleted.
+                           With \"--snapshot\" that snapshot is restored instead of the newest one,
+                           with \"--file\" only that file or subfolder of the game folder is restored,
+                           with \"--to\" the files of a single game are merged into DIR instead of its folder
+  snapshots NAME           List the snapshots of a game, newest first, with their index
+  files NAME [--snapshot ID|INDEX|DATE]
+                           List the files stored in the last snapshot of a game, or in the given one
+  rollback [--game NAME]...
+                           Undo the last restore, putting back the local files it replaced
+  list                     List the configured games
+  add NAME PATH [--include GLOB]... [--exclude GLOB]...
+                           Add a new game save folder, backing up only the files matching an
+                           include pattern (if any) and none of the exclude ones
+  remove NAME              Remove a game
+  compress                 Compress all saves into \"saves.zip\"
+  help                     Print this message";
+
+/// The operation ran but something went wrong.
+pub const EXIT_FAILURE: i32 = 1;
+/// The command line couldn't be understood.
+pub const EXIT_USAGE: i32 = 2;
+
+/// Runs a headless command and returns the process exit code.
+pub fn run(args: Vec<String>) -> i32 {
+    let json = args.iter().any(|arg| arg == "--json");
+    let mut args = args.into_iter().filter(|arg| arg != "--json");
+    let command = args.next().unwrap_or_default();
+    let args: Vec<String> = args.collect();
+    match command.as_str() {
+        "backup" => backup(args, json),
+        "diff" => diff(args, json),
+        "restore" => restore(args, json),
+        "rollback" => rollback(args, json),
+        "snapshots" => snapshots(args, json),
+        "files" => files(args, json),
+        "list" => list(args, json),
+        "add" => add(args, json),
+        "remove" => remove(args, json),
+        "compress" => compress(args, json),
+        "help" | "--help" | "-h" => {
+            println!("{USAGE}");
+            0
+        },
+        _ => usage(format!("Unknown command \"{command}\"."))
+    }
+}
+
+fn usage(msg: impl AsRef<str>) -> i32 {
+    eprintln!("{}\n\n{USAGE}", msg.as_ref());
+    EXIT_USAGE
+}
+
+/// The names given with "--game", or the exit code if anything else was given.
+fn game_args(args: Vec<String>) -> Result<Vec<String>, i32> {
+    let mut games = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--game" => match args.next() {
+                Some(game) => games.push(game),
+                None => return Err(usage("Missing game name after \"--game\"."))
+            },
+            _ => return Err(usage(format!("Unexpected argument \"{arg}\".")))
+        }
+    }
+    Ok(games)
+}
+
+fn backup(args: Vec<String>, json: bool) -> i32 {
+    let games = match game_args(args) { Ok(v) => v, Err(code) => return code };
+    let paths = match read_paths().and_then(|paths| select_games(&paths, &games)) { Some(v) => v, None => return EXIT_FAILURE };
+    let backend = match backend() { Some(v) => v, None => return EXIT_FAILURE };
+    run_with_logs(json, move |tx| run_backup(tx, backend.as_ref(), &paths))
+}
+
+fn diff(args: Vec<String>, json: bool) -> i32 {
+    let games = match game_args(args) { Ok(v) => v, Err(code) => return code };
+    let paths = match read_paths().and_then(|paths| select_games(&paths, &games)) { Some(v) => v, None => return EXIT_FAILURE };
+    let backend = match backend() { Some(v) => v, None => return EXIT_FAILURE };
+    run_with_logs(json, move |tx| run_diff(tx, backend.as_ref(), &paths))
+}
+
+fn restore(args: Vec<String>, json: bool) -> i32 {
+    let (mut yes, mut dry_run, mut snapshot, mut file, mut to) = (false, false, None, None, None);
+    let mut rest = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--yes" | "-y" => yes = true,
+            "--dry-run" => dry_run = true,
+            "--snapshot" => match args.next() {
+                Some(v) => snapshot = Some(v),
+                None => return usage("Missing snapshot after \"--snapshot\".")
+            },
+            "--file" => match args.next() {
+                Some(v) => file = Some(v),
+                None => return usage("Missing path after \"--file\".")
+            },
+            "--to" => match args.next() {
+                Some(v) => to = Some(PathBuf::from(v)),
+                None => return usage("Missing folder after \"--to\".")
+            },
+            _ => rest.push(arg)
+        }
+    }
+    let games = match game_args(rest) { Ok(v) => v, Err(code) => return code };
+    let paths = match read_paths().and_then(|paths| select_games(&paths, &games)) { Some(v) => v, None => return EXIT_FAILURE };
+    let backend = match backend() { Some(v) => v, None => return EXIT_FAILURE };
+    if to.is_some() && paths.len() != 1 { return usage("\"--to\" needs a single \"--game\".") }
+    let mut restores = Vec::new();
+    for path in paths {
+        let mut restore = match &snapshot {
+            Some(query) => match select_snapshot(backend.as_ref(), &path, Some(query)) {
+                Some(snapshot) => Restore::snapshot(path, snapshot),
+                None => return EXIT_FAILURE
+            },
+            None => Restore::latest(path)
+        };
+        if let Some(file) = &file { restore = restore.with_file(file.as_str()) }
+        if let Some(to) = &to { restore = restore.with_destination(to) }
+        restores.push(restore);
+    }
+    if dry_run || !yes {
+        let at_risk = Arc::new(AtomicBool::new(false));
+        let code = {
+            let (backend, restores, at_risk) = (backend.clone(), restores.clone(), at_risk.clone());
+            run_with_logs(json, move |tx| if run_restore_preview(tx, backend.as_ref(), &restores) {
+                at_risk.store(true, Ordering::Relaxed)
+            })
+        };
+        if code != 0 || dry_run { return code }
+        if at_risk.load(Ordering::Relaxed) && !confirm("Restore, replacing the local files listed above? [y/N] ") {
+            eprintln!("Restore cancelled.");
+            return EXIT_FAILURE
+        }
+    }
+    run_with_logs(json, move |tx| match SafetySnapshots::new() {
+        Ok(safety) => run_restore(tx, backend.as_ref(), &safety, &restores),
+        Err(e) => send(tx, Log::error(e.to_string()))
+    })
+}
+
+fn rollback(args: Vec<String>, json: bool) -> i32 {
+    let games = match game_args(args) { Ok(v) => v, Err(code) => return code };
+    let paths = match read_paths().and_then(|paths| select_games(&paths, &games)) { Some(v) => v, None => return EXIT_FAILURE };
+    run_with_logs(json, move |tx| match SafetySnapshots::new() {
+        Ok(safety) => run_rollback(tx, &safety, &paths),
+        Err(e) => send(tx, Log::error(e.to_string()))
+    })
+}
+
+fn snapshots(args: Vec<String>, json: bool) -> i32 {
+    let name = match args.as_slice() {
+        [name] => name.clone(),
+        _ => return usage("Expected a game name.")
+    };
+    let path = match read_paths().and_then(|paths| select_games(&paths, &[name])) { Some(mut v) => v.remove(0), None => return EXIT_FAILURE };
+    let backend = match backend() { Some(v) => v, None => return EXIT_FAILURE };
+    let snapshots = match backend.list_snapshots(&path) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            return EXIT_FAILURE
+        }
+    };
+    for (i, snapshot) in snapshots.iter().enumerate() {
+        if json {
+            println!("{}", serde_json::json!({
+                "index": i,
+                "id": snapshot.id,
+                "date": snapshot.date,
+                "message": snapshot.message,
+                "files": snapshot.files,
+                "size": snapshot.size
+            }));
+        } else {
+            println!(
+                "{i:>3}  {}  {}  {} files, {}  {}",
+                snapshot.date, snapshot.id, snapshot.files, format_size(snapshot.size), snapshot.message
+            );
+        }
+    }
+    0
+}
+
+fn files(args: Vec<String>, json: bool) -> i32 {
+    let (mut positional, mut query) = (Vec::new(), None);
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--snapshot" => match args.next() {
+                Some(v) => query = Some(v),
+                None => return usage("Missing snapshot after \"--snapshot\".")
+            },
+            _ => positional.push(arg)
+        }
+    }
+    let name = match positional.as_slice() {
+        [name] => name.clone(),
+        _ => return usage("Expected a game name.")
+    };
+    let path = match read_paths().and_then(|paths| select_games(&paths, &[name])) { Some(mut v) => v.remove(0), None => return EXIT_FAILURE };
+    let backend = match backend() { Some(v) => v, None => return EXIT_FAILURE };
+    let snapshot = match select_snapshot(backend.as_ref(), &path, query.as_deref()) { Some(v) => v, None => return EXIT_FAILURE };
+    let files = match list_snapshot_files(backend.as_ref(), &path, &snapshot) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            return EXIT_FAILURE
+        }
+    };
+    for file in files {
+        if json {
+            println!("{}", serde_json::json!({ "path": file.path, "is_dir": file.is_dir, "size": file.size }));
+        } else if file.is_dir {
+            println!("{}/", file.path);
+        } else {
+            println!("{}  {}", file.path, format_size(file.size));
+        }
+    }
+    0
+}
+
+/// Asks a yes/no question on the terminal, anything but "y" or "yes" (including no input at all) is a no.
+fn confirm(question: &str) -> bool {
+    eprint!("{question}");
+    std::io::stderr().flush().ok();
+    let mut answer = String::new();
+    if std::io::stdin().read_line(&mut answer).is_err() { return false }
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+fn list(args: Vec<String>, json: bool) -> i32 {
+    if let Some(arg) = args.first() { return usage(format!("Unexpected argument \"{arg}\".")) }
+    let paths = match read_paths() { Some(v) => v.paths, None => return EXIT_FAILURE };
+    for path in paths {
+        if json {
+            println!("{}", serde_json::json!({
+                "name": path.name,
+                "branch_name": path.branch_name,
+                "path": path.absolute_path,
+                "include": path.filter.include,
+                "exclude": path.filter.exclude
+            }));
+        } else {
+            println!("{} = {}", path.name, path.absolute_path.display());
+            for pattern in &path.filter.include { println!("    include = {pattern}") }
+            for pattern in &path.filter.exclude { println!("    exclude = {pattern}") }
+        }
+    }
+    0
+}
+
+fn add(args: Vec<String>, json: bool) -> i32 {
+    let (mut positional, mut include, mut exclude) = (Vec::new(), Vec::new(), Vec::new());
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let patterns = match arg.as_str() {
+            "--include" => &mut include,
+            "--exclude" => &mut exclude,
+            _ => { positional.push(arg); continue }
+        };
+        match args.next() {
+            Some(pattern) if !pattern.contains(['\r', '\n']) => patterns.push(pattern.trim().to_string()),
+            Some(_) => return usage("Patterns can not contain line breaks."),
+            None => return usage(format!("Missing pattern after \"{arg}\"."))
+        }
+    }
+    let (name, path) = match positional.as_slice() {
+        [name, path] => (name.trim(), PathBuf::from(path)),
+        _ => return usage("Expected a game name and a path.")
+    };
+    if let Err(e) = validate_name(name) { return usage(e.to_string()) }
+    let filter = match FileFilter::new(include, exclude) { Ok(v) => v, Err(e) => return usage(e.to_string()) };
+    let mut paths = match read_paths() { Some(v) => v, None => return EXIT_FAILURE };
+    if paths.find(name).is_some() {
+        eprintln!("\"{name}\" already exists.");
+        return EXIT_FAILURE
+    }
+    let path = std::fs::canonicalize(&path).unwrap_or(path);
+    if let Err(e) = paths.add(name, &path, filter) {
+        eprintln!("Error: {e}");
+        return EXIT_FAILURE
+    }
+    print_output(json, format!("Added \"{name}\" = {}", path.display()));
+    0
+}
+
+fn remove(args: Vec<String>, json: bool) -> i32 {
+    let name = match args.as_slice() {
+        [name] => name.trim(),
+        _ => return usage("Expected a game name.")
+    };
+    let mut paths = match read_paths() { Some(v) => v, None => return EXIT_FAILURE };
+    match paths.find(name) {
+        Some(index) => {
+            if let Err(e) = paths.remove(index) {
+                eprintln!("Error: {e}");
+                return EXIT_FAILURE
+            }
+            print_output(json, format!("Removed \"{name}\"."));
+            0
+        },
+        None => {
+            eprintln!("Unknown game \"{name}\".");
+            EXIT_FAILURE
+        }
+    }
+}
+
+fn compress(args: Vec<String>, json: bool) -> i32 {
+    if let Some(arg) = args.first() { return usage(format!("Unexpected argument \"{arg}\".")) }
+    let paths = match read_paths() { Some(v) => v.paths, None => return EXIT_FAILURE };
+    run_with_logs(json, move |tx| run_compress(tx, &paths))
+}
+
+/// Prints the result of a command that doesn't run a job, as an "output" event with "--json".
+fn print_output(json: bool, message: String) {
+    if json { println!("{}", Log::output(message).to_json()) } else { println!("{message}") }
+}
+
+fn read_paths() -> Option<Paths> {
+    Paths::read().map_err(|e| eprintln!("Error: {e}")).ok()
+}
+
+/// Picks the paths named in `games`, or every path if none was given.
+fn select_games(paths: &Paths, games: &[String]) -> Option<Vec<BackupPath>> {
+    if games.is_empty() { return Some(paths.paths.clone()) }
+    let mut selected = Vec::new();
+    for game in games {
+        match paths.find(game) {
+            Some(index) => selected.push(paths.paths[index].clone()),
+            None => {
+                eprintln!("Unknown game \"{game}\".");
+                return None
+            }
+        }
+    }
+    Some(selected)
+}
+
+/// The snapshot of `path` matching `query` (see `find_snapshot`), or its newest one without a query.
+fn select_snapshot(backend: &dyn StorageBackend, path: &BackupPath, query: Option<&str>) -> Option<Snapshot> {
+    let snapshots = match backend.list_snapshots(path) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            return None
+        }
+    };
+    let snapshot = match query {
+        Some(query) => find_snapshot(&snapshots, query),
+        None => snapshots.first()
+    };
+    match (snapshot, query) {
+        (Some(snapshot), _) => Some(snapshot.clone()),
+        (None, Some(query)) => {
+            eprintln!("No snapshot \"{query}\" found for \"{}\".", path.name);
+            None
+        },
+        (None, None) => {
+            eprintln!("No snapshots found for \"{}\".", path.name);
+            None
+        }
+    }
+}
+
+fn backend() -> Option<Arc<dyn StorageBackend>> {
+    let backend = match Backup::read() {
+        Ok(v) => v.backend(),
+        Err(e) => {
+            eprintln!("Error: {e}");
+            return None
+        }
+    };
+    if backend.is_none() {
+        eprintln!("No backup destination configured, run \"save-saver\" once to set it.");
+    }
+    backend
+}
+
+/// Runs `job` on a worker thread, printing its logs as they arrive.
+fn run_with_logs(json: bool, job: impl FnOnce(&LogSender) + Send + 'static) -> i32 {
+    let (tx, rx) = channel();
+    let worker = std::thread::spawn(move || {
+        job(&tx);
+        send(&tx, Log::Finished);
+    });
+    let mut failed = false;
+    for log in rx {
+        if let Log::Error { .. } = log { failed = true }
+        match &log {
+            _ if json => println!("{}", log.to_json()),
+            Log::FileCopied { .. } | Log::Finished => {},
+            Log::Error { .. } => eprintln!("{}", log.to_string().trim_end()),
+            _ => {
+                let text = log.to_string();
+                if !text.trim().is_empty() { println!("{}", text.trim_end()) }
+            }
+        }
+        if let Log::Finished = log { break }
+    }
+    if worker.join().is_err() { failed = true }
+    if json { return if failed { EXIT_FAILURE } else { 0 } }
+    if failed {
+        eprintln!("Finished with errors.");
+        EXIT_FAILURE
+    } else {
+        println!("Finished.");
+        0
+    }
+}
@@ -0,0 +1,86 @@
+use std::{collections::BTreeMap, path::{Path, PathBuf}};
+
+use serde::Serialize;
+use walkdir::WalkDir;
+
+use crate::{error::Error, filter::FileFilter};
+
+/// How a file differs between two folders, from the point of view of the one that gets replaced.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Change {
+    Added = 0,
+    Modified = 1,
+    Deleted = 2
+}
+
+pub struct FileChange {
+    /// Relative to the game folder, always with '/' separators.
+    pub path: String,
+    pub change: Change,
+    /// Of the current file, or of the stored one if it was deleted.
+    pub size: u64
+}
+
+pub struct FolderDiff {
+    /// Sorted by path.
+    pub changes: Vec<FileChange>,
+    pub unchanged: usize
+}
+
+/// Compares the files of `new` with the ones of `old`, a missing folder counts as an empty one.
+/// Files left out by `filter` are ignored on both sides.
+pub fn diff_folders(old: &Path, new: &Path, filter: &FileFilter) -> Result<FolderDiff, Error> {
+    diff_folders_under(old, new, "", filter)
+}
+
+/// Like `diff_folders`, but only for the file or subfolder `under` of both.
+pub fn diff_folders_under(old: &Path, new: &Path, under: &str, filter: &FileFilter) -> Result<FolderDiff, Error> {
+    let mut old_files = list_files(old, under, filter)?;
+    let mut diff = FolderDiff { changes: Vec::new(), unchanged: 0 };
+    for (path, new_file) in list_files(new, under, filter)? {
+        let size = std::fs::metadata(&new_file)?.len();
+        let change = match old_files.remove(&path) {
+            None => Change::Added,
+            Some(old_file) if !same_content(&old_file, &new_file)? => Change::Modified,
+            Some(_) => {
+                diff.unchanged += 1;
+                continue
+            }
+        };
+        diff.changes.push(FileChange { path, change, size });
+    }
+    for (path, old_file) in old_files {
+        let size = std::fs::metadata(&old_file)?.len();
+        diff.changes.push(FileChange { path, change: Change::Deleted, size });
+    }
+    diff.changes.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(diff)
+}
+
+/// Every file in `under` of `root` that `filter` keeps, by its path relative to `root`.
+fn list_files(root: &Path, under: &str, filter: &FileFilter) -> Result<BTreeMap<String, PathBuf>, Error> {
+    let mut files = BTreeMap::new();
+    let start = root.join(under);
+    if !start.exists() { return Ok(files) }
+    for entry in WalkDir::new(start) {
+        let entry = entry?;
+        if !entry.file_type().is_file() { continue }
+        let name = relative_name(entry.path(), root);
+        if filter.matches(&name) { files.insert(name, entry.into_path()); }
+    }
+    Ok(files)
+}
+
+/// `path` relative to `root`, with '/' separators.
+pub fn relative_name(path: &Path, root: &Path) -> String {
+    path.strip_prefix(root).unwrap_or(path).components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn same_content(a: &Path, b: &Path) -> Result<bool, Error> {
+    if std::fs::metadata(a)?.len() != std::fs::metadata(b)?.len() { return Ok(false) }
+    Ok(std::fs::read(a)? == std::fs::read(b)?)
+}
@@ -0,0 +1,64 @@
+use std::{fmt, path::PathBuf};
+
+use crate::storage::GitError;
+
+/// Everything that can go wrong reading the config, touching the saves or talking to a backend.
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    /// A line of "paths.txt" or "conf.txt" that can't be understood, `line` starts at 1.
+    Config { file: String, line: usize, message: String },
+    /// A game name that can't be written to "paths.txt".
+    InvalidName(String),
+    /// The folder of the "local" or "dedup" backend is missing, probably not mounted.
+    BackupFolderNotFound(PathBuf),
+    SnapshotNotFound(String),
+    /// A file or subfolder asked for that the snapshot doesn't have.
+    FileNotInSnapshot(String),
+    /// Neither the XDG variables nor $HOME say where the config and data folders are.
+    NoHomeFolder,
+    /// A path stored in a snapshot that would lead out of the folder it's restored to, e.g. "../.bashrc".
+    UnsafePath(String),
+    /// The files fetched for a restore don't add up to what the snapshot should contain.
+    IncompleteRestore { files: usize, size: u64, expected_files: usize, expected_size: u64 },
+    /// A snapshot manifest of the "dedup" backend that can't be read.
+    UnreadableManifest(PathBuf),
+    Git(GitError),
+    /// A request to the S3 storage failed.
+    S3(String),
+    Zip(zip::result::ZipError)
+}
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{e}"),
+            Self::Config { file, line, message } => write!(f, "\"{file}\" line {line}: {message}"),
+            Self::InvalidName(message) => write!(f, "{message}"),
+            Self::BackupFolderNotFound(path) => write!(f, "Backup folder \"{}\" not found", path.display()),
+            Self::SnapshotNotFound(id) => write!(f, "Snapshot \"{id}\" not found"),
+            Self::FileNotInSnapshot(file) => write!(f, "\"{file}\" is not in the snapshot"),
+            Self::NoHomeFolder => write!(f, "The home folder couldn't be found, please set $HOME"),
+            Self::UnsafePath(file) => write!(f, "\"{file}\" would be written outside of the restored folder"),
+            Self::IncompleteRestore { files, size, expected_files, expected_size } => write!(
+                f, "Got {files} files ({size} bytes) but the snapshot has {expected_files} files ({expected_size} bytes)"
+            ),
+            Self::UnreadableManifest(path) => write!(f, "Can't read \"{}\"", path.display()),
+            Self::Git(e) => write!(f, "{e}"),
+            Self::S3(message) => write!(f, "{message}"),
+            Self::Zip(e) => write!(f, "{e}")
+        }
+    }
+}
+impl std::error::Error for Error {}
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self { Self::Io(e) }
+}
+impl From<walkdir::Error> for Error {
+    fn from(e: walkdir::Error) -> Self { Self::Io(e.into()) }
+}
+impl From<GitError> for Error {
+    fn from(e: GitError) -> Self { Self::Git(e) }
+}
+impl From<zip::result::ZipError> for Error {
+    fn from(e: zip::result::ZipError) -> Self { Self::Zip(e) }
+}
@@ -0,0 +1,56 @@
+use std::path::{Path, PathBuf};
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use walkdir::WalkDir;
+
+use crate::diff::relative_name;
+
+/// Which files of a game folder are backed up, from the "include" and "exclude" lines of "paths.txt".
+///
+/// Patterns are matched against paths relative to the folder, with '/' separators,
+/// a pattern matching a folder applies to everything in it.
+#[derive(Clone)]
+pub struct FileFilter {
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    include_set: GlobSet,
+    exclude_set: GlobSet
+}
+impl FileFilter {
+    /// Keeps every file.
+    pub fn all() -> Self {
+        Self { include: Vec::new(), exclude: Vec::new(), include_set: GlobSet::empty(), exclude_set: GlobSet::empty() }
+    }
+    pub fn new(include: Vec<String>, exclude: Vec<String>) -> Result<Self, globset::Error> {
+        Ok(Self { include_set: glob_set(&include)?, exclude_set: glob_set(&exclude)?, include, exclude })
+    }
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+    /// Whether the file at `relative` is backed up: it matches an include pattern, if there are any, and no exclude one.
+    pub fn matches(&self, relative: &str) -> bool {
+        if self.is_empty() { return true }
+        let ancestors: Vec<&str> = relative.match_indices('/').map(|(i, _)| &relative[..i]).chain([relative]).collect();
+        let included = self.include.is_empty() || ancestors.iter().any(|path| self.include_set.is_match(path));
+        included && !ancestors.iter().any(|path| self.exclude_set.is_match(path))
+    }
+    /// The files under `root` that match, by their relative path, sorted.
+    pub fn files(&self, root: &Path) -> Result<Vec<(String, PathBuf)>, walkdir::Error> {
+        let mut files = Vec::new();
+        for entry in WalkDir::new(root).min_depth(1).sort_by_file_name() {
+            let entry = entry?;
+            if !entry.file_type().is_file() { continue }
+            let relative = relative_name(entry.path(), root);
+            if self.matches(&relative) { files.push((relative, entry.into_path())) }
+        }
+        Ok(files)
+    }
+}
+
+fn glob_set(patterns: &[String]) -> Result<GlobSet, globset::Error> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern)?);
+    }
+    builder.build()
+}
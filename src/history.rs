@@ -0,0 +1,237 @@
+use std::sync::{Arc, mpsc::{Receiver, channel, TryRecvError}};
+
+use tui::{
+    backend::Backend,
+    layout::{Constraint, Direction, Layout, Alignment, Rect},
+    widgets::{Paragraph, Borders, Block},
+    Frame, text::{Span, Spans}, style::{Style, Color}
+};
+
+use crate::{paths::BackupPath, storage::{StorageBackend, Snapshot}, restore::{SnapshotFile, list_snapshot_files}, error::Error, util::{scroll_offset, format_size}};
+
+pub struct History {
+    pub branch_name: Option<String>,
+    pub snapshots: Vec<Snapshot>,
+    pub selected_item: usize,
+    pub confirm_delete: bool,
+    /// Why the last load failed, if it did.
+    pub error: Option<Error>,
+    pub receive_snapshots: Option<Receiver<Result<Vec<Snapshot>, Error>>>,
+    /// The files of the selected snapshot, while browsing them.
+    pub files: Option<Vec<SnapshotFile>>,
+    pub selected_file: usize,
+    pub receive_files: Option<Receiver<Result<Vec<SnapshotFile>, Error>>>
+}
+impl History {
+    pub fn new() -> Self {
+        Self {
+            branch_name: None,
+            snapshots: Vec::new(),
+            selected_item: 0,
+            confirm_delete: false,
+            error: None,
+            receive_snapshots: None,
+            files: None,
+            selected_file: 0,
+            receive_files: None
+        }
+    }
+    /// Starts loading the snapshots of `path` in the background.
+    pub fn load(&mut self, backend: Option<Arc<dyn StorageBackend>>, path: Option<&BackupPath>) {
+        self.snapshots.clear();
+        self.selected_item = 0;
+        self.confirm_delete = false;
+        self.error = None;
+        self.close_files();
+        self.branch_name = path.map(|path| path.branch_name.clone());
+        let (backend, path) = match (backend, path) {
+            (Some(backend), Some(path)) => (backend, path.clone()),
+            _ => { self.receive_snapshots = None; return }
+        };
+        let (tx, rx) = channel();
+        self.receive_snapshots = Some(rx);
+        std::thread::spawn(move || {
+            tx.send(backend.list_snapshots(&path)).ok();
+        });
+    }
+    /// Whether snapshots or files are loading in the background, to redraw once they're in.
+    pub fn loading(&self) -> bool {
+        self.receive_snapshots.is_some() || self.receive_files.is_some()
+    }
+    /// Starts loading the files of the selected snapshot in the background, to pick some to restore.
+    pub fn browse(&mut self, backend: Option<Arc<dyn StorageBackend>>, path: Option<&BackupPath>) {
+        let (backend, path, snapshot) = match (backend, path, self.selected()) {
+            (Some(backend), Some(path), Some(snapshot)) => (backend, path.clone(), snapshot.clone()),
+            _ => return
+        };
+        self.files = Some(Vec::new());
+        self.selected_file = 0;
+        self.error = None;
+        let (tx, rx) = channel();
+        self.receive_files = Some(rx);
+        std::thread::spawn(move || {
+            tx.send(list_snapshot_files(backend.as_ref(), &path, &snapshot)).ok();
+        });
+    }
+    pub fn browsing(&self) -> bool {
+        self.files.is_some()
+    }
+    pub fn close_files(&mut self) {
+        if self.files.take().is_some() { self.error = None }
+        self.receive_files = None;
+    }
+    pub fn selected_file(&self) -> Option<&SnapshotFile> {
+        self.files.as_ref()?.get(self.selected_file)
+    }
+    /// Picks up the snapshots once the background load is done.
+    pub fn poll(&mut self) {
+        if let Some(rx) = self.receive_snapshots.as_ref() {
+            match rx.try_recv() {
+                Ok(result) => {
+                    match result {
+                        Ok(snapshots) => self.snapshots = snapshots,
+                        Err(e) => self.error = Some(e)
+                    }
+                    self.receive_snapshots = None
+                },
+                Err(TryRecvError::Empty) => {},
+                Err(TryRecvError::Disconnected) => self.receive_snapshots = None
+            }
+        }
+        if let Some(rx) = self.receive_files.as_ref() {
+            match rx.try_recv() {
+                Ok(result) => {
+                    match result {
+                        Ok(files) => self.files = Some(files),
+                        Err(e) => self.error = Some(e)
+                    }
+                    self.receive_files = None
+                },
+                Err(TryRecvError::Empty) => {},
+                Err(TryRecvError::Disconnected) => self.receive_files = None
+            }
+        }
+    }
+    pub fn selected(&self) -> Option<&Snapshot> {
+        self.snapshots.get(self.selected_item)
+    }
+    pub fn render(&mut self, f: &mut Frame<impl Backend>, area: Rect, path: Option<&BackupPath>) {
+        self.poll();
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Min(3),
+                Constraint::Percentage(100)
+            ])
+            .split(area);
+
+        let title = match path {
+            Some(path) => format!("Game: {}", path.name),
+            None => "No game selected, add one in the \"Paths\" tab.".to_string()
+        };
+        let help = if self.confirm_delete {
+            vec![Spans::from(Span::styled(
+                "Delete the selected snapshot from the remote? Press \"Y\" to confirm or any other key to cancel.",
+                Style::default().fg(Color::Yellow)
+            ))]
+        } else if self.browsing() {
+            vec![
+                Spans::from("Press \"R\" to restore only the selected file or folder, or \"O\" to put it in another folder."),
+                Spans::from("Press \"Esc\" to go back to the snapshots.")
+            ]
+        } else {
+            vec![
+                Spans::from("Press \"Tab\" to switch game, \"X\" to delete the selected snapshot, or \"F\" to reload."),
+                Spans::from("Press \"R\" to restore it, \"O\" to put it in another folder, or \"Enter\" to browse its files.")
+            ]
+        };
+        f.render_widget(Paragraph::new([vec![Spans::from(title)], help].concat()), chunks[0]);
+
+        let layout = Layout::default()
+            .direction(Direction::Vertical)
+            .vertical_margin(2)
+            .horizontal_margin(4)
+            .constraints([Constraint::Percentage(100)])
+            .split(chunks[1])[0];
+        if self.browsing() {
+            self.render_files(f, layout);
+            f.render_widget(Block::default().title("Files").borders(Borders::ALL), chunks[1]);
+            return
+        }
+        let mut spans = vec![];
+        if self.receive_snapshots.is_some() {
+            spans.push(Spans::from("Loading snapshots ..."));
+        } else if let Some(e) = &self.error {
+            spans.push(Spans::from(Span::styled(format!("Error: {e}"), Style::default().fg(Color::Red))));
+        } else if self.snapshots.is_empty() {
+            spans.push(Spans::from("No snapshots found."));
+        }
+        let mut iter = self.snapshots.iter();
+        let scroll = scroll_offset(self.selected_item, self.snapshots.len(), layout.height as usize);
+        iter.advance_by(scroll).unwrap();
+        for (i, snapshot) in iter.enumerate() {
+            const C: u8 = 50;
+            let color = if i + scroll == self.selected_item { Color::Rgb(C, C, C) } else { Color::Reset };
+            spans.push(Spans::from(
+                Span::styled(
+                    format!(
+                        " {}  {:>6} files  {:>10}  {} ",
+                        snapshot.date, snapshot.files, format_size(snapshot.size), snapshot.message
+                    ),
+                    Style::default().bg(color).fg(Color::White)
+                )
+            ));
+        }
+        f.render_widget(Paragraph::new(spans).alignment(Alignment::Left), layout);
+
+        f.render_widget(Block::default().title("Snapshots").borders(Borders::ALL), chunks[1]);
+    }
+    fn render_files(&self, f: &mut Frame<impl Backend>, area: Rect) {
+        let files = match &self.files { Some(v) => v, None => return };
+        let mut spans = vec![];
+        if self.receive_files.is_some() {
+            spans.push(Spans::from("Loading files ..."));
+        } else if let Some(e) = &self.error {
+            spans.push(Spans::from(Span::styled(format!("Error: {e}"), Style::default().fg(Color::Red))));
+        } else if files.is_empty() {
+            spans.push(Spans::from("The snapshot is empty."));
+        }
+        let mut iter = files.iter();
+        let scroll = scroll_offset(self.selected_file, files.len(), area.height as usize);
+        iter.advance_by(scroll).unwrap();
+        for (i, file) in iter.enumerate() {
+            const C: u8 = 50;
+            let color = if i + scroll == self.selected_file { Color::Rgb(C, C, C) } else { Color::Reset };
+            let depth = file.path.matches('/').count();
+            let name = file.path.rsplit('/').next().unwrap_or_default();
+            let text = if file.is_dir {
+                format!(" {}{name}/ ", "  ".repeat(depth))
+            } else {
+                format!(" {}{name}  {} ", "  ".repeat(depth), format_size(file.size))
+            };
+            spans.push(Spans::from(Span::styled(text, Style::default().bg(color).fg(Color::White))));
+        }
+        f.render_widget(Paragraph::new(spans).alignment(Alignment::Left), area);
+    }
+    pub fn scroll_down(&mut self) {
+        if self.snapshots.is_empty() { return }
+        if self.selected_item < self.snapshots.len() - 1 { self.selected_item += 1 }
+        else { self.selected_item = 0 }
+    }
+    pub fn scroll_up(&mut self) {
+        if self.snapshots.is_empty() { return }
+        if self.selected_item > 0 { self.selected_item -= 1 }
+        else { self.selected_item = self.snapshots.len() - 1 }
+    }
+    pub fn scroll_files_down(&mut self) {
+        let length = match &self.files { Some(v) if !v.is_empty() => v.len(), _ => return };
+        if self.selected_file < length - 1 { self.selected_file += 1 }
+        else { self.selected_file = 0 }
+    }
+    pub fn scroll_files_up(&mut self) {
+        let length = match &self.files { Some(v) if !v.is_empty() => v.len(), _ => return };
+        if self.selected_file > 0 { self.selected_file -= 1 }
+        else { self.selected_file = length - 1 }
+    }
+}
+
@@ -0,0 +1,68 @@
+use std::{path::{Path, PathBuf}, sync::OnceLock};
+
+use crate::error::Error;
+
+/// Set by "--config", used instead of the XDG config folder.
+static CONFIG_DIR: OnceLock<PathBuf> = OnceLock::new();
+/// Files that used to be read from the current directory.
+const CONFIG_FILES: [&str; 2] = ["paths.txt", "conf.txt"];
+
+/// Uses `dir` for "paths.txt" and "conf.txt", must be called before anything is read.
+pub fn set_config_dir(dir: PathBuf) {
+    CONFIG_DIR.set(dir).ok();
+}
+
+/// The "save-saver" folder in the one named by the XDG variable `var`, or in `fallback` in the home folder
+/// if it isn't set. The same on every OS, so the folders are where the README says.
+fn xdg_dir(var: &str, fallback: &str) -> Result<PathBuf, Error> {
+    let dir = match std::env::var_os(var).map(PathBuf::from) {
+        // Relative paths are invalid and should be ignored, says the spec
+        Some(dir) if dir.is_absolute() => dir,
+        _ => dirs::home_dir().ok_or(Error::NoHomeFolder)?.join(fallback)
+    };
+    Ok(dir.join("save-saver"))
+}
+
+/// Where "paths.txt" and "conf.txt" live, "$XDG_CONFIG_HOME/save-saver" (or "~/.config/save-saver")
+/// unless set with "--config".
+pub fn config_dir() -> Result<PathBuf, Error> {
+    match CONFIG_DIR.get() {
+        Some(dir) => Ok(dir.clone()),
+        None => xdg_dir("XDG_CONFIG_HOME", ".config")
+    }
+}
+
+pub fn config_file(name: &str) -> Result<PathBuf, Error> {
+    Ok(config_dir()?.join(name))
+}
+
+/// Files kept between runs, e.g. the pre-restore snapshots, in "$XDG_DATA_HOME/save-saver" (or "~/.local/share/save-saver").
+pub fn data_dir() -> Result<PathBuf, Error> {
+    xdg_dir("XDG_DATA_HOME", ".local/share")
+}
+
+/// Files that can be thrown away, e.g. the logs of the last run, in "$XDG_CACHE_HOME/save-saver" (or "~/.cache/save-saver").
+pub fn cache_dir() -> Result<PathBuf, Error> {
+    xdg_dir("XDG_CACHE_HOME", ".cache")
+}
+
+/// Copies "paths.txt" and "conf.txt" from the current directory to the config folder,
+/// unless it already has its own or was set with "--config". Returns a message for every file copied.
+///
+/// The old files are left where they are, they may belong to something else.
+pub fn migrate() -> Vec<String> {
+    if CONFIG_DIR.get().is_some() { return Vec::new() }
+    // Reported by whatever reads the config next
+    let dir = match config_dir() { Ok(v) => v, Err(_) => return Vec::new() };
+    let mut copied = Vec::new();
+    for name in CONFIG_FILES {
+        let (old, new) = (Path::new(".").join(name), dir.join(name));
+        if !old.is_file() || new.exists() { continue }
+        let result = std::fs::create_dir_all(&dir).and_then(|_| std::fs::copy(&old, &new));
+        copied.push(match result {
+            Ok(_) => format!("Copied \"{}\" to \"{}\", the old file isn't used anymore.", old.display(), new.display()),
+            Err(e) => format!("Couldn't copy \"{}\" to \"{}\": {e}", old.display(), new.display())
+        });
+    }
+    copied
+}
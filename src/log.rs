@@ -0,0 +1,95 @@
+use std::{fmt, path::PathBuf, sync::mpsc::Sender};
+
+use serde::Serialize;
+
+use crate::{diff::Change, util::format_size};
+
+/// A single event of a backup, restore or other background job.
+///
+/// The TUI renders these as text, the CLI prints them as text or JSON lines.
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Log {
+    /// A new step started.
+    Step { message: String },
+    /// Result of a step, e.g. the commit that was pushed.
+    Output { message: String },
+    /// A file was copied.
+    FileCopied { from: PathBuf, to: PathBuf, size: u64 },
+    /// A file a backup or restore would change, `path` is relative to the game folder.
+    /// `newer` if restoring would replace or remove a local file changed after the snapshot was taken.
+    FileChanged { game: String, path: String, change: Change, size: u64, newer: bool },
+    /// Something went wrong.
+    Error { message: String },
+    /// Which games of a backup or restore went through, sent right before `Finished`.
+    Summary { succeeded: Vec<String>, failed: Vec<String>, skipped: Vec<String> },
+    /// The job is done, nothing else will be sent.
+    Finished
+}
+impl Log {
+    pub fn step(message: impl Into<String>) -> Self {
+        Self::Step { message: message.into() }
+    }
+    pub fn output(message: impl Into<String>) -> Self {
+        Self::Output { message: message.into() }
+    }
+    pub fn error(message: impl Into<String>) -> Self {
+        Self::Error { message: message.into() }
+    }
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+impl fmt::Display for Log {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Step { message } | Self::Output { message } => write!(f, "{message}"),
+            Self::FileCopied { from, to, .. } => write!(f, "Copied \"{}\" to \"{}\"", from.display(), to.display()),
+            Self::FileChanged { path, change, size, newer, .. } => {
+                let symbol = match change { Change::Added => '+', Change::Modified => '~', Change::Deleted => '-' };
+                write!(f, "  {symbol} {path} ({})", format_size(*size))?;
+                if *newer { write!(f, " newer than the snapshot!")? }
+                Ok(())
+            },
+            Self::Error { message } => write!(f, "Error: {message}"),
+            Self::Summary { succeeded, failed, skipped } => {
+                let list = |names: &Vec<String>| if names.is_empty() { "none".to_string() } else { names.join(", ") };
+                write!(f, "Succeeded: {}\nFailed: {}\nSkipped: {}", list(succeeded), list(failed), list(skipped))
+            },
+            Self::Finished => write!(f, "Finished")
+        }
+    }
+}
+
+pub type LogSender = Sender<Log>;
+
+/// Which games of a backup or restore went through so far, sent as a `Log::Summary` once the job is done.
+#[derive(Default)]
+pub struct Summary {
+    succeeded: Vec<String>,
+    failed: Vec<String>,
+    skipped: Vec<String>
+}
+impl Summary {
+    pub fn succeed(&mut self, name: &str) {
+        self.succeeded.push(name.to_string())
+    }
+    /// Reports why the game `name` failed.
+    pub fn fail(&mut self, tx: &LogSender, name: &str, error: impl fmt::Display) {
+        send(tx, Log::error(format!("\"{name}\": {error}")));
+        self.failed.push(name.to_string())
+    }
+    pub fn skip(&mut self, name: &str) {
+        self.skipped.push(name.to_string())
+    }
+    pub fn any_failed(&self) -> bool {
+        !self.failed.is_empty()
+    }
+    pub fn send(self, tx: &LogSender) {
+        send(tx, Log::Summary { succeeded: self.succeeded, failed: self.failed, skipped: self.skipped })
+    }
+}
+
+pub fn send(tx: &LogSender, log: Log) {
+    tx.send(log).ok();
+}
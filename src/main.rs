@@ -1,21 +1,68 @@
 #![feature(iter_advance_by)]
 
-use std::error::Error;
+use std::{error::Error, time::Duration};
 use app::App;
-use crossterm::{terminal::{enable_raw_mode, EnterAlternateScreen}, execute, event::{Event, KeyCode, self}};
+use crossterm::{terminal::{enable_raw_mode, disable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen}, execute, cursor::Show, event::{Event, KeyCode, self}};
+use backup::Backup;
 use paths::Paths;
 use tui::{backend::CrosstermBackend, Terminal};
 
 mod app;
+mod cli;
 mod paths;
 mod backup;
+mod history;
+mod log;
+mod storage;
+mod error;
+mod diff;
+mod restore;
+mod filter;
+mod locations;
+mod util;
+#[cfg(test)]
+mod test_util;
+
+/// Puts the terminal back in its normal mode when dropped, even if the loop returned an error.
+struct TerminalGuard;
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        restore_terminal()
+    }
+}
+fn restore_terminal() {
+    disable_raw_mode().ok();
+    execute!(std::io::stdout(), LeaveAlternateScreen, Show).ok();
+}
 
 fn main() -> Result<(), Box<dyn Error>> {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(i) = args.iter().position(|arg| arg == "--config") {
+        if i + 1 >= args.len() {
+            eprintln!("Missing folder after \"--config\".");
+            std::process::exit(cli::EXIT_USAGE)
+        }
+        locations::set_config_dir(args.remove(i + 1).into());
+        args.remove(i);
+    }
+    for message in locations::migrate() { eprintln!("{message}") }
+    if !args.is_empty() {
+        std::process::exit(cli::run(args))
+    }
+
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        // Background jobs report their panics in the logs, printing them here would garble the screen
+        if std::thread::current().name() != Some("main") { return }
+        restore_terminal();
+        default_hook(info)
+    }));
     enable_raw_mode()?;
+    let _guard = TerminalGuard;
     let mut stdout = std::io::stdout();
     execute!(stdout, EnterAlternateScreen)?;
     let mut terminal = Terminal::new(CrosstermBackend::new(stdout))?;
-    terminal.hide_cursor().unwrap();
+    terminal.hide_cursor()?;
 
     let mut app = App::new();
     
@@ -24,7 +71,14 @@ fn main() -> Result<(), Box<dyn Error>> {
             terminal.draw(|f| app.render(f))?;
         }
         terminal.draw(|f| app.render(f))?;
+        if app.current_tab == 3 && app.history.loading() && !event::poll(Duration::from_millis(100))? {
+            continue
+        }
         let key = if let Event::Key(key) = event::read()? { key } else { continue };
+        if app.error.is_some() {
+            app.error = None;
+            continue
+        }
         match app.current_tab {
             0 => match key.code {
                 KeyCode::Char('q') | KeyCode::Char('c') => return Ok(()),
@@ -38,7 +92,10 @@ fn main() -> Result<(), Box<dyn Error>> {
                 match key.code {
                     KeyCode::Char(c) => app.paths.capturing_input.as_mut().unwrap().push(c),
                     KeyCode::Backspace => {app.paths.capturing_input.as_mut().unwrap().pop();},
-                    KeyCode::Enter => app.paths.add_new(),
+                    KeyCode::Enter => {
+                        let result = app.paths.add_new();
+                        app.report(result)
+                    },
                     _ => {}
                 }
             } else {
@@ -51,16 +108,42 @@ fn main() -> Result<(), Box<dyn Error>> {
                     KeyCode::Char('n') => if app.current_tab == 1 {
                         app.paths.dialog_add_new()
                     },
-                    KeyCode::Char('r') => app.paths.delete_selected(),
-                    KeyCode::Char('f') => app.paths = Paths::read(),
+                    KeyCode::Char(' ') => app.paths.toggle_selected(),
+                    KeyCode::Char('r') => {
+                        let result = app.paths.delete_selected();
+                        app.report(result)
+                    },
+                    KeyCode::Char('f') => {
+                        let result = Paths::read().map(|paths| app.paths = paths);
+                        app.report(result)
+                    },
                     _ => {}
                 }
             },
-            2 => if app.backup.repo_url.is_none() {
+            2 => if app.backup.choosing_snapshot.is_some() {
                 match key.code {
                     KeyCode::Char(c) => app.backup.text_input.push(c),
                     KeyCode::Backspace => {app.backup.text_input.pop();},
-                    KeyCode::Enter => app.backup.set_repo_url(),
+                    KeyCode::Enter => app.backup.confirm_restore_snapshot(),
+                    KeyCode::Esc => {
+                        app.backup.choosing_snapshot = None;
+                        app.backup.text_input.clear()
+                    },
+                    _ => {}
+                }
+            } else if app.backup.pending_restore.is_some() {
+                match key.code {
+                    KeyCode::Char('y') => app.backup.confirm_restore(),
+                    _ => app.backup.cancel_restore()
+                }
+            } else if app.backup.target().is_none() {
+                match key.code {
+                    KeyCode::Char(c) => app.backup.text_input.push(c),
+                    KeyCode::Backspace => {app.backup.text_input.pop();},
+                    KeyCode::Enter => {
+                        let result = app.backup.set_repo_url();
+                        app.report(result)
+                    },
                     _ => {}
                 }
             } else {
@@ -70,17 +153,67 @@ fn main() -> Result<(), Box<dyn Error>> {
                     KeyCode::Char('a') | KeyCode::Left => app.previous(),
                     KeyCode::Char('s') | KeyCode::Down => app.paths.scroll_down(),
                     KeyCode::Char('d') | KeyCode::Right => app.next(),
-                    KeyCode::Char('r') => if !app.backup.downloading {
-                        app.backup.restore(app.paths.paths.clone())
+                    KeyCode::Char('r') => if !app.backup.downloading && !app.backup.previewing {
+                        app.backup.restore(app.paths.chosen())
                     },
                     KeyCode::Enter | KeyCode::Char('e') => if app.backup.uploading {
                         app.backup.uploading = false
                     } else if app.backup.downloading {
                         app.backup.downloading = false
+                    } else if app.backup.previewing {
+                        app.backup.previewing = false
                     } else {
-                        app.backup.backup(app.paths.paths.clone())
+                        app.backup.backup(app.paths.chosen())
+                    },
+                    KeyCode::Char('u') => if !app.backup.downloading {
+                        app.backup.rollback(app.paths.chosen())
+                    },
+                    KeyCode::Char('p') => if !app.backup.previewing {
+                        app.backup.preview(app.paths.chosen())
+                    },
+                    KeyCode::Char('t') => if !app.backup.uploading {
+                        app.backup.compress(app.paths.paths.clone())
+                    },
+                    KeyCode::Char('v') => if let Some(path) = app.paths.paths.get(app.paths.selected_item) {
+                        let result = app.backup.dialog_restore_snapshot(path.clone());
+                        app.report(result)
+                    },
+                    _ => {}
+                }
+            },
+            3 => if app.history.browsing() {
+                match key.code {
+                    KeyCode::Char('q') | KeyCode::Char('c') => return Ok(()),
+                    KeyCode::Char('w') | KeyCode::Up => app.history.scroll_files_up(),
+                    KeyCode::Char('s') | KeyCode::Down => app.history.scroll_files_down(),
+                    KeyCode::Esc | KeyCode::Backspace => app.history.close_files(),
+                    KeyCode::Char('r') => app.restore_selected(None),
+                    KeyCode::Char('o') => app.restore_selected_elsewhere(),
+                    _ => {}
+                }
+            } else if app.history.confirm_delete {
+                app.history.confirm_delete = false;
+                if key.code == KeyCode::Char('y') {
+                    app.with_selected_snapshot(Backup::delete_snapshot)
+                }
+            } else {
+                match key.code {
+                    KeyCode::Char('q') | KeyCode::Char('c') => return Ok(()),
+                    KeyCode::Char('w') | KeyCode::Up => app.history.scroll_up(),
+                    KeyCode::Char('a') | KeyCode::Left => app.previous(),
+                    KeyCode::Char('s') | KeyCode::Down => app.history.scroll_down(),
+                    KeyCode::Char('d') | KeyCode::Right => app.next(),
+                    KeyCode::Tab => {
+                        app.paths.scroll_down();
+                        app.reload_history()
+                    },
+                    KeyCode::Char('f') => app.reload_history(),
+                    KeyCode::Enter => app.history.browse(app.backup.backend(), app.paths.paths.get(app.paths.selected_item)),
+                    KeyCode::Char('r') => app.with_selected_snapshot(Backup::restore_snapshot),
+                    KeyCode::Char('o') => app.restore_selected_elsewhere(),
+                    KeyCode::Char('x') | KeyCode::Delete if app.history.selected().is_some() => {
+                        app.history.confirm_delete = true
                     },
-                    KeyCode::Char('t') => app.backup.compress(app.paths.paths.clone()),
                     _ => {}
                 }
             },
@@ -1,20 +1,23 @@
-use std::{path::{PathBuf, Path}, io::Write};
+use std::{path::{PathBuf, Path}, io::Write, collections::HashSet};
 
 use tui::{
     backend::Backend,
     layout::{Constraint, Direction, Layout, Alignment, Rect},
-    widgets::{Paragraph, Borders, Block, Clear},
+    widgets::{Paragraph, Borders, Block, Clear, Wrap},
     Frame, text::{Span, Spans}, style::{Style, Color}
 };
 
-const PATH: &'static str = "./paths.txt";
+use crate::{error::Error, filter::FileFilter, locations::{config_dir, config_file}, util::scroll_offset};
+
+const FILE: &str = "paths.txt";
 
 #[derive(Clone)]
 pub struct BackupPath {
     pub name: String,
     pub branch_name: String,
     pub absolute_path: PathBuf,
-    pub relative_path: PathBuf
+    pub relative_path: PathBuf,
+    pub filter: FileFilter
 }
 impl BackupPath {
     pub fn new(name: impl AsRef<str>, path: impl AsRef<Path>) -> Self {
@@ -24,55 +27,93 @@ impl BackupPath {
             branch_name: name.replace(' ', "-").to_lowercase(),
             name,
             absolute_path: format_path_to_absolute(path.clone()),
-            relative_path: format_path_to_relative(path)
+            relative_path: format_path_to_relative(path),
+            filter: FileFilter::all()
         }
     }
+    pub fn with_filter(self, filter: FileFilter) -> Self {
+        Self { filter, ..self }
+    }
+    /// The files of the game folder that are backed up, by their relative path.
+    pub fn files(&self) -> Result<Vec<(String, PathBuf)>, walkdir::Error> {
+        self.filter.files(&self.absolute_path)
+    }
+    /// Writes the "paths.txt" lines of this game.
+    fn write(&self, file: &mut impl Write) -> std::io::Result<()> {
+        write!(file, "{} = {}\r\n", self.name, self.relative_path.display())?;
+        for pattern in &self.filter.include { write!(file, "    include = {pattern}\r\n")? }
+        for pattern in &self.filter.exclude { write!(file, "    exclude = {pattern}\r\n")? }
+        Ok(())
+    }
 }
 
 pub struct Paths {
     pub paths: Vec<BackupPath>,
     pub selected_item: usize,
+    /// Branch names of the games ticked for the next backup or restore.
+    pub checked: HashSet<String>,
     pub add_new_dialog_folder: Option<PathBuf>,
     pub capturing_input: Option<String>
 }
 impl Paths {
-    pub fn read() -> Self {
-        let paths = if Path::new(PATH).exists() {
+    pub fn new(paths: Vec<BackupPath>) -> Self {
+        Self {
+            paths,
+            selected_item: 0,
+            checked: HashSet::new(),
+            add_new_dialog_folder: None,
+            capturing_input: None
+        }
+    }
+    pub fn read() -> Result<Self, Error> {
+        let config = config_file(FILE)?;
+        let paths = if config.exists() {
             let mut paths = Vec::new();
-            for line in std::fs::read_to_string(PATH).unwrap().lines() {
-                if line.len() == 0 { continue }
-                let mut line = line.split('=');
-                let name = line.next().unwrap().trim();
-                let path = PathBuf::from(line.next().unwrap().trim());
-                paths.push(BackupPath::new(name, path))
+            for (i, line) in std::fs::read_to_string(&config)?.lines().enumerate() {
+                if line.trim().is_empty() { continue }
+                let config_error = |message: &str| Error::Config { file: config.display().to_string(), line: i + 1, message: message.to_string() };
+                // Indented lines are the filters of the game above them
+                if line.starts_with([' ', '\t']) {
+                    let path: &mut BackupPath = paths.last_mut().ok_or_else(|| config_error("filter without a game above it"))?;
+                    let (mut include, mut exclude) = (path.filter.include.clone(), path.filter.exclude.clone());
+                    match line.split_once('=').map(|(key, pattern)| (key.trim(), pattern.trim().to_string())) {
+                        Some(("include", pattern)) => include.push(pattern),
+                        Some(("exclude", pattern)) => exclude.push(pattern),
+                        _ => return Err(config_error("expected \"include = GLOB\" or \"exclude = GLOB\""))
+                    }
+                    path.filter = FileFilter::new(include, exclude).map_err(|e| config_error(&e.to_string()))?;
+                    continue
+                }
+                let (name, path) = line.split_once('=').ok_or_else(|| config_error("expected \"NAME = PATH\""))?;
+                if name.trim().is_empty() { return Err(config_error("missing the game name")) }
+                paths.push(BackupPath::new(name.trim(), PathBuf::from(path.trim())))
             }
             paths
         } else { 
+            std::fs::create_dir_all(config_dir()?)?;
             std::fs::OpenOptions::new()
                 .create_new(true)
                 .write(true)
-                .open(PATH).unwrap()
-                .write_all(b"").unwrap();
+                .open(&config)?
+                .write_all(b"")?;
             vec![]
         };
-        Self {
-            paths,
-            selected_item: 0,
-            add_new_dialog_folder: None,
-            capturing_input: None
-        }
+        Ok(Self::new(paths))
     }
     pub fn render(&self, f: &mut Frame<impl Backend>, area: Rect) {
         let chunks = Layout::default()
             .direction(Direction::Vertical)
             .constraints([
-                Constraint::Min(2),
+                Constraint::Min(3),
                 Constraint::Percentage(100)
             ])
             .split(area);
 
         f.render_widget(
-            Paragraph::new("Press \"N\" to add a new path, \"R\" to remove the selected path, or \"F\" to reload."),
+            Paragraph::new(vec![
+                Spans::from("Press \"N\" to add a new path, \"R\" to remove the selected path, or \"F\" to reload."),
+                Spans::from("Press \"Space\" to tick the games to backup or restore, all of them are used if none is ticked.")
+            ]),
             chunks[0]
         );
 
@@ -84,25 +125,18 @@ impl Paths {
             .split(chunks[1])[0];
         let mut spans = vec![];
         let mut iter = self.paths.iter();
-        let mut scroll = 0;
-        let length = self.paths.len();
-        if length > layout.height as usize {
-            let h2 = (layout.height / 2) as usize;
-            if self.selected_item > h2 {
-                if self.selected_item < length - h2 {
-                    scroll = self.selected_item - h2;
-                } else {
-                    scroll = length - layout.height as usize;
-                }
-                iter.advance_by(scroll).unwrap();
-            }
-        }
+        let scroll = scroll_offset(self.selected_item, self.paths.len(), layout.height as usize);
+        iter.advance_by(scroll).unwrap();
         for (i, path) in iter.enumerate() {
             const C: u8 = 50;
             let color = if i + scroll == self.selected_item { Color::Rgb(C, C, C) } else { Color::Reset };
             spans.push(Spans::from(
                 Span::styled(
-                    format!(" {}: {} ", path.name, path.absolute_path.display()),
+                    format!(
+                        " [{}] {}: {} {}",
+                        if self.checked.contains(&path.branch_name) { "x" } else { " " }, path.name, path.absolute_path.display(),
+                        format_filter(&path.filter)
+                    ),
                     Style::default().bg(color).fg(Color::White)
                 )
             ));
@@ -139,7 +173,7 @@ impl Paths {
                     Span::from("> "),
                     Span::from(input)
                 ])
-            ]),
+            ]).wrap(Wrap { trim: true }),
             Layout::default()
                 .direction(Direction::Vertical)
                 .margin(3)
@@ -149,61 +183,101 @@ impl Paths {
                 .split(area)[0]
         );
     }
-    pub fn add_new(&mut self) {
-        let path = self.add_new_dialog_folder.take().expect("No folder selected");
-        let name = self.capturing_input.take().expect("Name can not be empty");
-        if name.len() == 0 { panic!("Name can not be empty.") }
-        
+    pub fn add_new(&mut self) -> Result<(), Error> {
+        let path = match self.add_new_dialog_folder.take() { Some(v) => v, None => return Ok(()) };
+        let name = self.capturing_input.take().unwrap_or_default();
+        self.add(name, path, FileFilter::all())
+    }
+    pub fn add(&mut self, name: impl AsRef<str>, path: impl AsRef<Path>, filter: FileFilter) -> Result<(), Error> {
+        let name = name.as_ref().trim().to_string();
+        validate_name(&name)?;
+        let path = BackupPath::new(name, format_path_to_relative(path)).with_filter(filter);
         let mut file = std::fs::OpenOptions::new()
             .append(true)
-            .write(true)
             .create(true)
-            .open(PATH).unwrap();
-        write!(file, "\r\n{} = {}", name, path.display()).unwrap();
+            .open(config_file(FILE)?)?;
+        write!(file, "\r\n")?;
+        path.write(&mut file)?;
 
-        self.paths.push(BackupPath::new(name, path));
+        self.paths.push(path);
+        Ok(())
     }
     pub fn scroll_down(&mut self) {
+        if self.paths.is_empty() { return }
         if self.selected_item < self.paths.len() - 1 { self.selected_item += 1 }
         else { self.selected_item = 0 }
     }
     pub fn scroll_up(&mut self) {
+        if self.paths.is_empty() { return }
         if self.selected_item > 0 { self.selected_item -= 1 }
         else { self.selected_item = self.paths.len() - 1 }
     }
-    pub fn delete_selected(&mut self) {
-        if self.paths.len() == 0 { return }
-        self.paths.remove(self.selected_item);
+    pub fn toggle_selected(&mut self) {
+        let branch_name = match self.paths.get(self.selected_item) { Some(v) => v.branch_name.clone(), None => return };
+        if !self.checked.remove(&branch_name) { self.checked.insert(branch_name); }
+    }
+    /// The ticked games, or every game if none is.
+    pub fn chosen(&self) -> Vec<BackupPath> {
+        let checked: Vec<BackupPath> = self.paths.iter().filter(|path| self.checked.contains(&path.branch_name)).cloned().collect();
+        if checked.is_empty() { self.paths.clone() } else { checked }
+    }
+    pub fn delete_selected(&mut self) -> Result<(), Error> {
+        if self.paths.is_empty() { return Ok(()) }
+        self.remove(self.selected_item)
+    }
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.paths.iter().position(|path| path.name.eq_ignore_ascii_case(name) || path.branch_name == name)
+    }
+    pub fn remove(&mut self, index: usize) -> Result<(), Error> {
+        self.paths.remove(index);
+        if self.selected_item >= self.paths.len() { self.selected_item = self.paths.len().saturating_sub(1) }
         let mut file = std::fs::OpenOptions::new()
             .create(true)
             .write(true)
             .append(false)
             .truncate(true)
-            .open(PATH).unwrap();
+            .open(config_file(FILE)?)?;
         for path in &self.paths {
-            write!(file, "{} = {}\r\n", path.name, path.relative_path.display()).unwrap();
+            path.write(&mut file)?;
         }
+        Ok(())
     }
 }
 
-fn format_path_to_relative(path: impl AsRef<Path>) -> PathBuf {
+/// Checks that `name` fits in a line of "paths.txt".
+pub fn validate_name(name: &str) -> Result<(), Error> {
+    if name.trim().is_empty() { return Err(Error::InvalidName("Name can not be empty.".to_string())) }
+    if name.contains('=') { return Err(Error::InvalidName("Name can not contain \"=\".".to_string())) }
+    if name.contains(['\r', '\n']) { return Err(Error::InvalidName("Name can not contain line breaks.".to_string())) }
+    Ok(())
+}
+
+/// The patterns of `filter` for a line of the "Paths" tab, empty if it keeps every file.
+fn format_filter(filter: &FileFilter) -> String {
+    let mut text = String::new();
+    if !filter.include.is_empty() { text += &format!("include: {} ", filter.include.join(", ")) }
+    if !filter.exclude.is_empty() { text += &format!("exclude: {} ", filter.exclude.join(", ")) }
+    text
+}
+
+pub fn format_path_to_relative(path: impl AsRef<Path>) -> PathBuf {
     let mut path = path.as_ref().to_path_buf();
-    let home_dir = dirs::home_dir().unwrap();
-    if path.starts_with(&home_dir) {
-        path = PathBuf::from("$HOME").join(path.strip_prefix(&home_dir).unwrap());
+    if let Some(home_dir) = dirs::home_dir() {
+        if path.starts_with(&home_dir) {
+            path = PathBuf::from("$HOME").join(path.strip_prefix(&home_dir).unwrap());
+        }
     }
     path
 }
 pub fn format_path_to_absolute(path: impl AsRef<Path>) -> PathBuf {
     let path = path.as_ref();
-    if path.starts_with("$HOME") {
-        let home_dir = dirs::home_dir().unwrap();
+    if let (true, Some(home_dir)) = (path.starts_with("$HOME"), dirs::home_dir()) {
         return home_dir.join(path.strip_prefix("$HOME").unwrap())
     }
     path.to_path_buf()
 }
 
-fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
+pub fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
     let vertical_area = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
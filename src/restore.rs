@@ -0,0 +1,644 @@
+use std::{path::{Path, PathBuf}, sync::mpsc::channel};
+
+use chrono::{DateTime, FixedOffset, Utc};
+use tempfile::TempDir;
+use walkdir::WalkDir;
+
+use crate::{
+    paths::BackupPath,
+    log::{Log, LogSender, Summary, send},
+    storage::{StorageBackend, Snapshot, LocalBackend, stays_inside},
+    diff::{Change, diff_folders_under, relative_name},
+    backup::copy_folder_files_to_folder,
+    filter::FileFilter,
+    locations::data_dir,
+    error::Error
+};
+
+/// A game to restore and from which snapshot.
+#[derive(Clone)]
+pub struct Restore {
+    pub path: BackupPath,
+    /// The newest one if `None`.
+    pub snapshot: Option<Snapshot>,
+    /// A single file or subfolder to restore, relative to the game folder with '/' separators.
+    /// The whole folder is replaced if `None`.
+    pub file: Option<String>,
+    /// Where to put the files instead of the game folder. They are merged into it,
+    /// the files the snapshot doesn't have are kept.
+    pub destination: Option<PathBuf>
+}
+impl Restore {
+    pub fn latest(path: BackupPath) -> Self {
+        Self { path, snapshot: None, file: None, destination: None }
+    }
+    pub fn snapshot(path: BackupPath, snapshot: Snapshot) -> Self {
+        Self { path, snapshot: Some(snapshot), file: None, destination: None }
+    }
+    pub fn with_file(self, file: impl Into<String>) -> Self {
+        Self { file: Some(file.into()), ..self }
+    }
+    pub fn with_destination(self, destination: impl Into<PathBuf>) -> Self {
+        Self { destination: Some(destination.into()), ..self }
+    }
+    /// The folder the files are restored into.
+    pub fn target(&self) -> &Path {
+        self.destination.as_deref().unwrap_or(&self.path.absolute_path)
+    }
+    /// The destination as a game of its own, to keep a copy of its files before they are overwritten,
+    /// apart from the ones of other destinations.
+    fn destination_path(&self) -> Option<BackupPath> {
+        let destination = self.destination.as_ref()?;
+        let folder: String = destination.display().to_string().chars()
+            .map(|c| if c.is_alphanumeric() { c } else { '-' })
+            .collect();
+        Some(BackupPath {
+            name: destination.display().to_string(),
+            branch_name: format!("{}-elsewhere/{}", self.path.branch_name, folder.trim_matches('-')),
+            absolute_path: destination.clone(),
+            relative_path: destination.clone(),
+            filter: FileFilter::all()
+        })
+    }
+    /// The chosen snapshot, or the newest one if the game has any.
+    fn resolve(&self, backend: &dyn StorageBackend) -> Result<Option<Snapshot>, Error> {
+        match &self.snapshot {
+            Some(snapshot) => Ok(Some(snapshot.clone())),
+            None => Ok(backend.list_snapshots(&self.path)?.into_iter().next())
+        }
+    }
+}
+
+/// Pre-restore snapshots kept for every game.
+const KEEP_SAFETY_SNAPSHOTS: usize = 5;
+/// Lists the games of the last restore, one branch name per line.
+const LAST_RESTORE: &str = "last-restore.txt";
+
+/// Copies of the local files a restore replaced, so it can be rolled back.
+///
+/// These always stay on this computer, whatever backend is configured.
+pub struct SafetySnapshots {
+    pub backend: LocalBackend
+}
+impl SafetySnapshots {
+    /// The ones in "pre-restore" in the data folder.
+    pub fn new() -> Result<Self, Error> {
+        Ok(Self::at(data_dir()?.join("pre-restore")))
+    }
+    pub fn at(root: impl Into<PathBuf>) -> Self {
+        Self { backend: LocalBackend::new(root) }
+    }
+    /// Stores the current files of `path`, dropping the oldest pre-restore snapshots.
+    pub fn take(&self, tx: &LogSender, path: &BackupPath) -> Result<(), Error> {
+        self.take_files(tx, path, path.files()?)
+    }
+    /// Like `take`, with only `files` of `path`.
+    pub fn take_files(&self, tx: &LogSender, path: &BackupPath, files: Vec<(String, PathBuf)>) -> Result<(), Error> {
+        send(tx, Log::step(format!(
+            "Keeping a copy of the current files of \"{}\" in \"{}\" ...", path.name, self.backend.root.join(&path.branch_name).display()
+        )));
+        std::fs::create_dir_all(&self.backend.root)?;
+        self.backend.put_files(tx, path, files)?;
+        let (quiet, _) = channel();
+        for snapshot in self.backend.list_snapshots(path)?.iter().skip(KEEP_SAFETY_SNAPSHOTS) {
+            self.backend.delete_snapshot(&quiet, path, snapshot)?;
+        }
+        Ok(())
+    }
+    /// Remembers which games the last restore replaced.
+    pub fn set_last_restore(&self, paths: &[BackupPath]) -> Result<(), Error> {
+        let lines: Vec<&str> = paths.iter().map(|path| path.branch_name.as_str()).collect();
+        std::fs::write(self.backend.root.join(LAST_RESTORE), lines.join("\n"))?;
+        Ok(())
+    }
+    /// Branch names of the games the last restore replaced, if it wasn't rolled back yet.
+    pub fn last_restore(&self) -> Vec<String> {
+        std::fs::read_to_string(self.backend.root.join(LAST_RESTORE)).unwrap_or_default()
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+/// Restores every game, blocking until done.
+///
+/// The local files of each game are kept as a pre-restore snapshot first, a game is skipped if that fails.
+/// The same goes for the files a restore to another folder overwrites, but those aren't put back by a rollback.
+pub fn run_restore(tx: &LogSender, backend: &dyn StorageBackend, safety: &SafetySnapshots, restores: &[Restore]) {
+    let mut summary = Summary::default();
+    let mut replaced = Vec::new();
+    for restore in restores {
+        let path = &restore.path;
+        send(tx, Log::step(format!("Looking for the snapshot of \"{}\" ...", path.name)));
+        let result = restore.resolve(backend).and_then(|snapshot| match snapshot {
+            Some(snapshot) => match restore.destination_path() {
+                Some(destination) => {
+                    let file = restore.file.as_deref().unwrap_or_default();
+                    merge_into(tx, backend, safety, path, &snapshot, file, &destination).map(|_| true)
+                },
+                None => {
+                    recover_interrupted(tx, &path.absolute_path)?;
+                    if let Some(file) = restore.file.as_ref().filter(|file| stays_inside(file)) {
+                        recover_interrupted(tx, &path.absolute_path.join(file))?
+                    }
+                    if path.absolute_path.exists() {
+                        safety.take(tx, path)?;
+                        replaced.push(path.clone());
+                    }
+                    match &restore.file {
+                        Some(file) => restore_file(tx, backend, path, &snapshot, file, &path.absolute_path),
+                        None => fetch_atomically(tx, backend, path, &snapshot, &path.absolute_path)
+                    }.map(|_| true)
+                }
+            },
+            None => Ok(false)
+        });
+        match result {
+            Ok(true) => summary.succeed(&path.name),
+            Ok(false) => {
+                send(tx, Log::step(format!("No snapshots found for \"{}\", skipping ...", path.name)));
+                summary.skip(&path.name)
+            },
+            Err(e) => summary.fail(tx, &path.name, e)
+        }
+    }
+    if !replaced.is_empty() {
+        match safety.set_last_restore(&replaced) {
+            Ok(()) => send(tx, Log::step("Press \"U\" in the \"Backup\" tab or run \"save-saver rollback\" to undo this restore.")),
+            Err(e) => send(tx, Log::error(e.to_string()))
+        }
+    }
+    summary.send(tx);
+}
+
+/// Puts back the files the last restore replaced, for each of `paths` it touched, blocking until done.
+///
+/// The pre-restore snapshots it used are removed, older ones are kept.
+pub fn run_rollback(tx: &LogSender, safety: &SafetySnapshots, paths: &[BackupPath]) {
+    let last_restore = safety.last_restore();
+    let paths: Vec<&BackupPath> = paths.iter().filter(|path| last_restore.contains(&path.branch_name)).collect();
+    if paths.is_empty() {
+        send(tx, Log::step("Nothing to roll back."));
+        return
+    }
+    let mut summary = Summary::default();
+    for path in paths {
+        let backend = &safety.backend;
+        let result = backend.list_snapshots(path).and_then(|snapshots| match snapshots.first() {
+            Some(snapshot) => {
+                send(tx, Log::step(format!("Rolling \"{}\" back to its files from {} ...", path.name, snapshot.date)));
+                fetch_atomically(tx, backend, path, snapshot, &path.absolute_path)?;
+                backend.delete_snapshot(tx, path, snapshot).map(|_| true)
+            },
+            None => Ok(false)
+        });
+        match result {
+            Ok(true) => summary.succeed(&path.name),
+            Ok(false) => {
+                send(tx, Log::step(format!("No pre-restore snapshot of \"{}\", skipping ...", path.name)));
+                summary.skip(&path.name)
+            },
+            Err(e) => summary.fail(tx, &path.name, e)
+        }
+    }
+    if !summary.any_failed() {
+        std::fs::remove_file(safety.backend.root.join(LAST_RESTORE)).ok();
+    }
+    summary.send(tx);
+}
+
+/// Lists what restoring would do to the local files without touching them, blocking until done.
+///
+/// `Added` files would be created, `Modified` ones overwritten and `Deleted` ones removed.
+/// Returns whether any local file would be overwritten or removed.
+pub fn run_restore_preview(tx: &LogSender, backend: &dyn StorageBackend, restores: &[Restore]) -> bool {
+    let mut at_risk = false;
+    for restore in restores {
+        let path = &restore.path;
+        let snapshot = match restore.resolve(backend) {
+            Ok(Some(v)) => v,
+            Ok(None) => {
+                send(tx, Log::step(format!("No snapshots found for \"{}\", skipping ...", path.name)));
+                continue
+            },
+            Err(e) => {
+                send(tx, Log::error(format!("\"{}\": {e}", path.name)));
+                continue
+            }
+        };
+        let file = restore.file.as_deref().unwrap_or_default();
+        let target = restore.target();
+        if let Some(destination) = &restore.destination {
+            send(tx, Log::step(format!(
+                "\"{}\" goes to \"{}\" instead of its own folder, the files already there that the snapshot doesn't have are kept.",
+                path.name, destination.display()
+            )));
+        }
+        send(tx, Log::step(match file {
+            "" => format!("Comparing \"{}\" with the snapshot from {} ...", path.name, snapshot.date),
+            _ => format!("Comparing \"{file}\" of \"{}\" with the snapshot from {} ...", path.name, snapshot.date)
+        }));
+        let diff = fetch_to_temp(backend, path, Some(&snapshot)).and_then(|(_temp, stored)| {
+            if !file.is_empty() { find_in_snapshot(&stored, file)?; }
+            diff_folders_under(target, &stored, file, &path.filter)
+        });
+        let diff = match diff {
+            Ok(v) => v,
+            Err(e) => {
+                send(tx, Log::error(format!("\"{}\": {e}", path.name)));
+                continue
+            }
+        };
+        let taken = DateTime::parse_from_rfc3339(&snapshot.date).ok();
+        let (mut counts, mut newer_count) = ([0; 3], 0);
+        for file in diff.changes {
+            // Nothing is deleted from another folder
+            if restore.destination.is_some() && file.change == Change::Deleted { continue }
+            counts[file.change as usize] += 1;
+            let newer = file.change != Change::Added && is_newer(&target.join(&file.path), taken);
+            if newer { newer_count += 1 }
+            send(tx, Log::FileChanged { game: path.name.clone(), path: file.path, change: file.change, size: file.size, newer });
+        }
+        at_risk |= counts[Change::Modified as usize] + counts[Change::Deleted as usize] > 0;
+        send(tx, Log::output(format!(
+            "{} to create, {} to overwrite, {} to delete, {} unchanged, {newer_count} local files newer than the snapshot",
+            counts[0], counts[1], counts[2], diff.unchanged
+        )));
+    }
+    at_risk
+}
+
+/// Fetches `snapshot` into a staging folder next to `to` and only swaps it in once it's complete,
+/// so an interrupted restore never leaves `to` half replaced.
+pub fn fetch_atomically(tx: &LogSender, backend: &dyn StorageBackend, path: &BackupPath, snapshot: &Snapshot, to: &Path) -> Result<(), Error> {
+    let staging = sibling(to, "staging");
+    recover_interrupted(tx, to)?;
+    if let Some(parent) = to.parent() { std::fs::create_dir_all(parent)? }
+
+    let fetched = backend.fetch_snapshot(tx, path, snapshot, &staging)
+        .and_then(|_| verify(&staging, snapshot))
+        .and_then(|_| keep_excluded(&path.filter, &staging, to, ""));
+    if let Err(e) = fetched {
+        std::fs::remove_dir_all(&staging).ok();
+        return Err(e)
+    }
+    swap_in(tx, &staging, to)
+}
+
+/// Puts back a single file or subfolder of `snapshot` into the `root` folder, leaving the rest of it as it is.
+fn restore_file(tx: &LogSender, backend: &dyn StorageBackend, path: &BackupPath, snapshot: &Snapshot, file: &str, root: &Path) -> Result<(), Error> {
+    send(tx, Log::step(format!("Fetching \"{file}\" of \"{}\" from the snapshot of {} ...", path.name, snapshot.date)));
+    let (_temp, stored) = fetch_to_temp(backend, path, Some(snapshot))?;
+    verify(&stored, snapshot)?;
+    let from = find_in_snapshot(&stored, file)?;
+    let to = root.join(file);
+    let staging = sibling(&to, "staging");
+    recover_interrupted(tx, &to)?;
+    if let Some(parent) = to.parent() { std::fs::create_dir_all(parent)? }
+    let copied = if from.is_dir() {
+        copy_folder_files_to_folder(tx, &from, &staging).and_then(|_| keep_excluded(&path.filter, &staging, &to, file))
+    } else {
+        std::fs::copy(&from, &staging).map(|size| send(tx, Log::FileCopied { from: from.clone(), to: to.clone(), size })).map_err(Error::from)
+    };
+    if let Err(e) = copied {
+        remove(&staging);
+        return Err(e)
+    }
+    swap_in(tx, &staging, &to)
+}
+
+/// Copies `file` of `snapshot`, or all of it if empty, into the `destination` folder,
+/// overwriting the files that are in both, once `safety` has a copy of them, and keeping the ones only `destination` has.
+fn merge_into(
+    tx: &LogSender, backend: &dyn StorageBackend, safety: &SafetySnapshots, path: &BackupPath, snapshot: &Snapshot, file: &str, destination: &BackupPath
+) -> Result<(), Error> {
+    let root = &destination.absolute_path;
+    send(tx, Log::step(format!("Fetching the snapshot of \"{}\" from {} ...", path.name, snapshot.date)));
+    let (_temp, stored) = fetch_to_temp(backend, path, Some(snapshot))?;
+    verify(&stored, snapshot)?;
+    let from = if file.is_empty() { stored } else { find_in_snapshot(&stored, file)? };
+    // An empty snapshot of a backend that doesn't create empty folders
+    if !from.exists() { return Ok(()) }
+    let files = if from.is_dir() { FileFilter::all().files(&from)? } else { vec![(String::new(), from)] };
+    // Where each file goes, relative to the destination
+    let files: Vec<(String, PathBuf)> = files.into_iter()
+        .map(|(relative, from)| (match (file, relative.as_str()) {
+            ("", _) => relative,
+            (_, "") => file.to_string(),
+            _ => format!("{file}/{relative}")
+        }, from))
+        .collect();
+    let overwritten: Vec<(String, PathBuf)> = files.iter()
+        .map(|(relative, _)| (relative.clone(), root.join(relative)))
+        .filter(|(_, target)| target.is_file())
+        .collect();
+    if !overwritten.is_empty() { safety.take_files(tx, destination, overwritten)? }
+
+    send(tx, Log::step(format!("Copying the restored files into \"{}\" ...", root.join(file).display())));
+    for (relative, from) in files {
+        let target = root.join(relative);
+        if let Some(parent) = target.parent() { std::fs::create_dir_all(parent)? }
+        let size = std::fs::copy(&from, &target)?;
+        send(tx, Log::FileCopied { from, to: target, size });
+    }
+    Ok(())
+}
+
+/// Drops the files `filter` leaves out from `staging` and copies the ones of `to` into it,
+/// so a restore only replaces the files that are backed up. `under` is where both are in the game folder.
+fn keep_excluded(filter: &FileFilter, staging: &Path, to: &Path, under: &str) -> Result<(), Error> {
+    if filter.is_empty() { return Ok(()) }
+    let in_game = |relative: &str| if under.is_empty() { relative.to_string() } else { format!("{under}/{relative}") };
+    for (relative, file) in FileFilter::all().files(staging)? {
+        if !filter.matches(&in_game(&relative)) { std::fs::remove_file(file)? }
+    }
+    if !to.is_dir() { return Ok(()) }
+    for (relative, file) in FileFilter::all().files(to)? {
+        if filter.matches(&in_game(&relative)) { continue }
+        let target = staging.join(&relative);
+        if let Some(parent) = target.parent() { std::fs::create_dir_all(parent)? }
+        std::fs::copy(&file, &target)?;
+    }
+    Ok(())
+}
+
+/// Replaces `to` with the complete `staging` file or folder, putting `to` back if that fails.
+///
+/// `to` is moved aside to a free "old" sibling first, all of them are only removed once the swap is done.
+fn swap_in(tx: &LogSender, staging: &Path, to: &Path) -> Result<(), Error> {
+    let stale = old_copies(to);
+    let old = old_copy(to, stale.len() + 1);
+    send(tx, Log::step(format!("Replacing \"{}\" with the restored files ...", to.display())));
+    if to.exists() { std::fs::rename(to, &old)? }
+    if let Err(e) = std::fs::rename(staging, to) {
+        if old.exists() {
+            if let Err(e) = std::fs::rename(&old, to) {
+                send(tx, Log::error(format!("Couldn't put back the original files, they are in \"{}\": {e}", old.display())));
+            }
+        }
+        remove(staging);
+        return Err(e.into())
+    }
+    for old in stale.iter().chain([&old]) { remove(old) }
+    Ok(())
+}
+
+/// Where `swap_in` moves `to` aside, the `n`th time in a row it was interrupted before cleaning up.
+fn old_copy(to: &Path, n: usize) -> PathBuf {
+    if n == 1 { sibling(to, "old") } else { sibling(to, &format!("old-{n}")) }
+}
+
+/// The copies of `to` that `swap_in` left behind, the newest last.
+fn old_copies(to: &Path) -> Vec<PathBuf> {
+    (1..).map(|n| old_copy(to, n)).take_while(|old| old.exists()).collect()
+}
+
+/// Undoes what a restore interrupted by a crash or power loss left next to `to`:
+/// the incomplete staging copy is removed, and if `to` was already moved aside it's put back,
+/// being the only copy of those files.
+fn recover_interrupted(tx: &LogSender, to: &Path) -> Result<(), Error> {
+    remove(&sibling(to, "staging"));
+    if to.exists() { return Ok(()) }
+    if let Some(old) = old_copies(to).pop() {
+        send(tx, Log::step(format!("Putting back \"{}\" from \"{}\", left by an interrupted restore ...", to.display(), old.display())));
+        std::fs::rename(&old, to)?;
+    }
+    Ok(())
+}
+
+/// Removes a file or folder, if there is one.
+fn remove(path: &Path) {
+    if path.is_dir() { std::fs::remove_dir_all(path).ok(); } else { std::fs::remove_file(path).ok(); }
+}
+
+/// Where `file` is in the fetched `stored` folder, refusing paths that would lead out of it.
+fn find_in_snapshot(stored: &Path, file: &str) -> Result<PathBuf, Error> {
+    let found = stored.join(file);
+    if !stays_inside(file) || file.is_empty() || !found.exists() { return Err(Error::FileNotInSnapshot(file.to_string())) }
+    Ok(found)
+}
+
+/// A file or folder stored in a snapshot.
+pub struct SnapshotFile {
+    /// Relative to the game folder, always with '/' separators.
+    pub path: String,
+    pub is_dir: bool,
+    /// 0 for folders.
+    pub size: u64
+}
+
+/// Lists everything stored in `snapshot` of `path`, folders before the files they contain.
+pub fn list_snapshot_files(backend: &dyn StorageBackend, path: &BackupPath, snapshot: &Snapshot) -> Result<Vec<SnapshotFile>, Error> {
+    let (_temp, stored) = fetch_to_temp(backend, path, Some(snapshot))?;
+    let mut files = Vec::new();
+    if !stored.exists() { return Ok(files) }
+    for entry in WalkDir::new(&stored).min_depth(1).sort_by_file_name() {
+        let entry = entry?;
+        let is_dir = entry.file_type().is_dir();
+        let size = if is_dir { 0 } else { entry.metadata()?.len() };
+        files.push(SnapshotFile { path: relative_name(entry.path(), &stored), is_dir, size });
+    }
+    Ok(files)
+}
+
+/// A hidden folder next to `to`, on the same drive so it can be renamed to `to`.
+fn sibling(to: &Path, suffix: &str) -> PathBuf {
+    let name = to.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+    to.with_file_name(format!(".{name}.save-saver-{suffix}"))
+}
+
+/// Checks that `dir` has as many files and bytes as `snapshot`.
+fn verify(dir: &Path, snapshot: &Snapshot) -> Result<(), Error> {
+    let (mut files, mut size) = (0, 0);
+    for entry in WalkDir::new(dir).min_depth(1) {
+        let entry = entry?;
+        if entry.file_type().is_file() {
+            files += 1;
+            size += entry.metadata()?.len();
+        }
+    }
+    if files != snapshot.files || size != snapshot.size {
+        return Err(Error::IncompleteRestore { files, size, expected_files: snapshot.files, expected_size: snapshot.size })
+    }
+    Ok(())
+}
+
+/// Downloads `snapshot` of `path` to a temp folder, returning the folder (removed when dropped) and where the files are.
+/// Without a snapshot the files folder doesn't exist, like for a game that was never backed up.
+pub fn fetch_to_temp(backend: &dyn StorageBackend, path: &BackupPath, snapshot: Option<&Snapshot>) -> Result<(TempDir, PathBuf), Error> {
+    let temp = tempfile::tempdir()?;
+    let stored = temp.path().join("snapshot");
+    if let Some(snapshot) = snapshot {
+        // The copied files are only an implementation detail here
+        let (quiet, _) = channel();
+        backend.fetch_snapshot(&quiet, path, snapshot, &stored)?;
+    }
+    Ok((temp, stored))
+}
+
+/// Whether `file` was modified after the snapshot was taken.
+fn is_newer(file: &Path, taken: Option<DateTime<FixedOffset>>) -> bool {
+    let (modified, taken) = match (std::fs::metadata(file).and_then(|metadata| metadata.modified()), taken) {
+        (Ok(modified), Some(taken)) => (DateTime::<Utc>::from(modified), taken),
+        _ => return false
+    };
+    modified > taken
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::channel;
+
+    use tempfile::TempDir;
+
+    use super::{Restore, SafetySnapshots, run_restore, run_rollback, sibling};
+    use crate::{
+        backup::run_backup,
+        filter::FileFilter,
+        log::{Log, LogSender},
+        storage::{StorageBackend, GitBackend, LocalBackend, DedupBackend},
+        test_util::{write, read_tree, fake_game}
+    };
+
+    /// Runs `job` and returns its logs, failing the test if any of them is an error.
+    fn run(job: impl FnOnce(&LogSender)) -> Vec<Log> {
+        let (tx, rx) = channel();
+        job(&tx);
+        drop(tx);
+        let logs: Vec<Log> = rx.iter().collect();
+        let errors: Vec<String> = logs.iter().filter(|log| matches!(log, Log::Error { .. })).map(Log::to_string).collect();
+        assert!(errors.is_empty(), "{errors:?}");
+        logs
+    }
+
+    /// Backs up a game, changes every kind of file, restores it and rolls the restore back.
+    fn round_trip(dir: &TempDir, backend: &dyn StorageBackend) {
+        let path = fake_game(dir);
+        let safety = SafetySnapshots::at(dir.path().join("pre-restore"));
+        let original = read_tree(&path.absolute_path);
+        run(|tx| run_backup(tx, backend, std::slice::from_ref(&path)));
+
+        write(&path.absolute_path, "slot1.sav", b"overwritten");
+        std::fs::remove_file(path.absolute_path.join("profiles/player.dat")).unwrap();
+        write(&path.absolute_path, "new/slot2.sav", b"not in the snapshot");
+        let changed = read_tree(&path.absolute_path);
+
+        let logs = run(|tx| run_restore(tx, backend, &safety, &[Restore::latest(path.clone())]));
+        assert!(logs.iter().any(|log| matches!(log, Log::Summary { succeeded, .. } if succeeded == &["Test Game"])));
+        assert_eq!(read_tree(&path.absolute_path), original);
+
+        run(|tx| run_rollback(tx, &safety, std::slice::from_ref(&path)));
+        assert_eq!(read_tree(&path.absolute_path), changed);
+    }
+
+    #[test]
+    fn git_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let remote = dir.path().join("remote.git");
+        git2::Repository::init_bare(&remote).unwrap();
+        round_trip(&dir, &GitBackend::new(remote.display().to_string()));
+    }
+
+    #[test]
+    fn local_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("store")).unwrap();
+        round_trip(&dir, &LocalBackend::new(dir.path().join("store")));
+    }
+
+    #[test]
+    fn dedup_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("store")).unwrap();
+        round_trip(&dir, &DedupBackend::new(dir.path().join("store")));
+    }
+
+    #[test]
+    fn restores_a_single_file() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("store")).unwrap();
+        let backend = LocalBackend::new(dir.path().join("store"));
+        let safety = SafetySnapshots::at(dir.path().join("pre-restore"));
+        let path = fake_game(&dir);
+        run(|tx| run_backup(tx, &backend, std::slice::from_ref(&path)));
+
+        write(&path.absolute_path, "slot1.sav", b"corrupt");
+        write(&path.absolute_path, "profiles/player.dat", b"newer");
+        run(|tx| run_restore(tx, &backend, &safety, &[Restore::latest(path.clone()).with_file("slot1.sav")]));
+        assert_eq!(std::fs::read(path.absolute_path.join("slot1.sav")).unwrap(), b"first slot");
+        assert_eq!(std::fs::read(path.absolute_path.join("profiles/player.dat")).unwrap(), b"newer");
+    }
+
+    #[test]
+    fn restore_keeps_excluded_files() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("store")).unwrap();
+        let backend = LocalBackend::new(dir.path().join("store"));
+        let safety = SafetySnapshots::at(dir.path().join("pre-restore"));
+        let path = fake_game(&dir).with_filter(FileFilter::new(Vec::new(), vec!["cache".to_string()]).unwrap());
+        write(&path.absolute_path, "cache/shaders.bin", b"cache");
+        run(|tx| run_backup(tx, &backend, std::slice::from_ref(&path)));
+        assert!(!read_tree(&backend.root).keys().any(|file| file.contains("shaders")));
+
+        write(&path.absolute_path, "cache/shaders.bin", b"rebuilt cache");
+        write(&path.absolute_path, "slot1.sav", b"overwritten");
+        run(|tx| run_restore(tx, &backend, &safety, &[Restore::latest(path.clone())]));
+        assert_eq!(std::fs::read(path.absolute_path.join("slot1.sav")).unwrap(), b"first slot");
+        assert_eq!(std::fs::read(path.absolute_path.join("cache/shaders.bin")).unwrap(), b"rebuilt cache");
+    }
+
+    #[test]
+    fn restore_elsewhere_keeps_the_files_already_there() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("store")).unwrap();
+        let backend = LocalBackend::new(dir.path().join("store"));
+        let safety = SafetySnapshots::at(dir.path().join("pre-restore"));
+        let path = fake_game(&dir);
+        run(|tx| run_backup(tx, &backend, std::slice::from_ref(&path)));
+
+        let elsewhere = dir.path().join("elsewhere");
+        write(&elsewhere, "slot1.sav", b"other game");
+        write(&elsewhere, "notes.txt", b"unrelated");
+        run(|tx| run_restore(tx, &backend, &safety, &[Restore::latest(path.clone()).with_destination(&elsewhere)]));
+        let mut expected = read_tree(&path.absolute_path);
+        expected.insert("notes.txt".to_string(), b"unrelated".to_vec());
+        assert_eq!(read_tree(&elsewhere), expected);
+
+        // Only the overwritten files are kept, apart from other destinations, and aren't put back over the game folder by a rollback
+        let copies = read_tree(&safety.backend.root.join("test-game-elsewhere"));
+        assert_eq!(copies.len(), 1, "{copies:?}");
+        let (copy, content) = copies.iter().next().unwrap();
+        assert!(copy.split('/').next().unwrap().ends_with("-elsewhere") && copy.ends_with("/slot1.sav"), "{copy}");
+        assert_eq!(content, b"other game");
+        assert!(safety.last_restore().is_empty());
+    }
+
+    #[test]
+    fn restore_recovers_an_interrupted_swap() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("store")).unwrap();
+        let backend = LocalBackend::new(dir.path().join("store"));
+        let safety = SafetySnapshots::at(dir.path().join("pre-restore"));
+        let path = fake_game(&dir);
+        let original = read_tree(&path.absolute_path);
+        run(|tx| run_backup(tx, &backend, std::slice::from_ref(&path)));
+        write(&path.absolute_path, "slot1.sav", b"played since");
+        let changed = read_tree(&path.absolute_path);
+
+        // Stopped after moving the game folder aside, before the restored files were moved in
+        std::fs::rename(&path.absolute_path, sibling(&path.absolute_path, "old")).unwrap();
+        write(&sibling(&path.absolute_path, "staging"), "slot1.sav", b"half");
+        run(|tx| run_restore(tx, &backend, &safety, &[Restore::latest(path.clone())]));
+        assert_eq!(read_tree(&path.absolute_path), original);
+        assert!(!sibling(&path.absolute_path, "old").exists() && !sibling(&path.absolute_path, "staging").exists());
+        run(|tx| run_rollback(tx, &safety, std::slice::from_ref(&path)));
+        assert_eq!(read_tree(&path.absolute_path), changed);
+
+        // Stopped after the swap, before the old files were removed
+        write(&sibling(&path.absolute_path, "old"), "slot1.sav", b"stale");
+        run(|tx| run_restore(tx, &backend, &safety, &[Restore::latest(path.clone())]));
+        assert_eq!(read_tree(&path.absolute_path), original);
+        assert!(!sibling(&path.absolute_path, "old").exists() && !sibling(&path.absolute_path, "old-2").exists());
+    }
+}
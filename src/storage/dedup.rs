@@ -0,0 +1,212 @@
+use std::{path::{Path, PathBuf}, collections::HashSet, fs::{File, OpenOptions}};
+
+use serde::{Serialize, Deserialize};
+use sha2::{Sha256, Digest};
+use walkdir::WalkDir;
+
+use crate::{paths::BackupPath, log::{Log, LogSender, send}, error::Error};
+use super::{StorageBackend, Snapshot, new_snapshot_id, snapshot_date, check_root, stays_inside, join_inside};
+
+/// A snapshot: which stored object goes where.
+#[derive(Serialize, Deserialize)]
+struct Manifest {
+    files: Vec<ManifestFile>
+}
+#[derive(Serialize, Deserialize)]
+struct ManifestFile {
+    /// Relative to the game folder, always with '/' separators.
+    path: String,
+    hash: String,
+    size: u64
+}
+
+/// Locked while objects are added or removed, see `DedupBackend::lock`.
+const LOCK_FILE: &str = "lock";
+
+/// Content-addressed store in a local folder.
+///
+/// Every file is stored once in `<root>/objects/<hash>`, no matter how many snapshots or games have it,
+/// and each snapshot is a manifest in `<root>/snapshots/<branch_name>/<timestamp>.json`.
+pub struct DedupBackend {
+    pub root: PathBuf
+}
+impl DedupBackend {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+    fn objects_dir(&self) -> PathBuf {
+        self.root.join("objects")
+    }
+    fn object_path(&self, hash: &str) -> PathBuf {
+        self.objects_dir().join(&hash[..2]).join(hash)
+    }
+    fn snapshots_dir(&self) -> PathBuf {
+        self.root.join("snapshots")
+    }
+    fn manifest_path(&self, path: &BackupPath, id: &str) -> PathBuf {
+        self.snapshots_dir().join(&path.branch_name).join(format!("{id}.json"))
+    }
+    /// The manifest at `manifest_path`, if it can be read and only has paths and hashes this backend writes.
+    fn read_manifest(&self, manifest_path: &Path) -> Option<Manifest> {
+        let manifest: Manifest = serde_json::from_slice(&std::fs::read(manifest_path).ok()?).ok()?;
+        let valid = manifest.files.iter().all(|file| {
+            stays_inside(&file.path) && file.hash.len() == 64 && file.hash.bytes().all(|byte| byte.is_ascii_hexdigit())
+        });
+        valid.then_some(manifest)
+    }
+    /// Locks the store until the returned file is dropped, waiting for other processes that hold it.
+    ///
+    /// Snapshots are stored with a `shared` lock and removed with an exclusive one, so the garbage collection
+    /// never sees the objects of a snapshot whose manifest isn't written yet.
+    fn lock(&self, tx: &LogSender, shared: bool) -> Result<File, Error> {
+        let file = OpenOptions::new().create(true).truncate(false).write(true).open(self.root.join(LOCK_FILE))?;
+        let locked = if shared { file.try_lock_shared().is_ok() } else { file.try_lock().is_ok() };
+        if !locked {
+            send(tx, Log::step(format!("Waiting for another save-saver using \"{}\" ...", self.root.display())));
+            if shared { file.lock_shared()? } else { file.lock()? }
+        }
+        Ok(file)
+    }
+    /// Removes every object no manifest points to anymore.
+    fn collect_garbage(&self, tx: &LogSender) -> Result<(), Error> {
+        let mut used = HashSet::new();
+        for entry in WalkDir::new(self.snapshots_dir()).into_iter().filter_map(Result::ok) {
+            if entry.path().extension() != Some("json".as_ref()) { continue }
+            match self.read_manifest(entry.path()) {
+                Some(manifest) => used.extend(manifest.files.into_iter().map(|file| file.hash)),
+                // Better to keep some garbage than to lose files of a snapshot we can't read
+                None => return Err(Error::UnreadableManifest(entry.path().to_path_buf()))
+            }
+        }
+        let (mut removed, mut freed) = (0, 0);
+        for entry in WalkDir::new(self.objects_dir()).min_depth(2).into_iter().filter_map(Result::ok) {
+            let hash = entry.file_name().to_string_lossy();
+            if used.contains(hash.as_ref()) { continue }
+            freed += entry.metadata().map(|metadata| metadata.len()).unwrap_or_default();
+            if std::fs::remove_file(entry.path()).is_ok() { removed += 1 }
+        }
+        send(tx, Log::step(format!("Removed {removed} unused files ({freed} bytes)")));
+        Ok(())
+    }
+}
+impl StorageBackend for DedupBackend {
+    fn put_snapshot(&self, tx: &LogSender, path: &BackupPath) -> Result<(), Error> {
+        check_root(&self.root)?;
+        let _lock = self.lock(tx, true)?;
+        send(tx, Log::step(format!("Storing files from \"{}\" ...", path.absolute_path.display())));
+        let mut manifest = Manifest { files: Vec::new() };
+        let (mut new_files, mut new_bytes, mut total_bytes) = (0, 0, 0);
+        for (relative, file) in path.files()? {
+            let hash = hash_file(&file)?;
+            let size = std::fs::metadata(&file)?.len();
+            let object = self.object_path(&hash);
+            if !object.exists() {
+                let partial = object.with_extension("partial");
+                std::fs::create_dir_all(object.parent().unwrap())?;
+                std::fs::copy(&file, &partial)?;
+                std::fs::rename(&partial, &object)?;
+                send(tx, Log::FileCopied { from: file, to: object, size });
+                new_files += 1;
+                new_bytes += size;
+            }
+            total_bytes += size;
+            manifest.files.push(ManifestFile { path: relative, hash, size });
+        }
+        let id = new_snapshot_id();
+        let manifest_path = self.manifest_path(path, &id);
+        std::fs::create_dir_all(manifest_path.parent().unwrap())?;
+        let partial = manifest_path.with_extension("partial");
+        std::fs::write(&partial, serde_json::to_vec_pretty(&manifest).unwrap())?;
+        std::fs::rename(&partial, &manifest_path)?;
+        send(tx, Log::step(format!(
+            "Stored {} files ({total_bytes} bytes), {new_files} of them new ({new_bytes} bytes)",
+            manifest.files.len()
+        )));
+        Ok(())
+    }
+    fn list_snapshots(&self, path: &BackupPath) -> Result<Vec<Snapshot>, Error> {
+        let dir = match std::fs::read_dir(self.snapshots_dir().join(&path.branch_name)) { Ok(v) => v, Err(_) => return Ok(Vec::new()) };
+        let mut snapshots: Vec<Snapshot> = dir.filter_map(|entry| {
+            let entry = entry.ok()?;
+            let id = entry.file_name().into_string().ok()?.strip_suffix(".json")?.to_string();
+            let date = snapshot_date(&id)?;
+            let manifest = self.read_manifest(&entry.path())?;
+            Some(Snapshot {
+                date,
+                message: "Deduplicated snapshot".to_string(),
+                files: manifest.files.len(),
+                size: manifest.files.iter().map(|file| file.size).sum(),
+                id
+            })
+        }).collect();
+        snapshots.sort_by(|a, b| b.id.cmp(&a.id));
+        Ok(snapshots)
+    }
+    fn fetch_snapshot(&self, tx: &LogSender, path: &BackupPath, snapshot: &Snapshot, to: &Path) -> Result<(), Error> {
+        let manifest_path = self.manifest_path(path, &snapshot.id);
+        let manifest = match self.read_manifest(&manifest_path) {
+            Some(v) => v,
+            None if manifest_path.exists() => return Err(Error::UnreadableManifest(manifest_path)),
+            None => return Err(Error::SnapshotNotFound(snapshot.id.clone()))
+        };
+        send(tx, Log::step(format!("Copying snapshot {} to \"{}\" ...", snapshot.id, to.display())));
+        std::fs::remove_dir_all(to).ok();
+        std::fs::create_dir_all(to)?;
+        for file in manifest.files {
+            let target = join_inside(to, &file.path)?;
+            std::fs::create_dir_all(target.parent().unwrap())?;
+            let object = self.object_path(&file.hash);
+            std::fs::copy(&object, &target)?;
+            send(tx, Log::FileCopied { from: object, to: target, size: file.size });
+        }
+        Ok(())
+    }
+    fn delete_snapshot(&self, tx: &LogSender, path: &BackupPath, snapshot: &Snapshot) -> Result<(), Error> {
+        let _lock = self.lock(tx, false)?;
+        let manifest_path = self.manifest_path(path, &snapshot.id);
+        send(tx, Log::step(format!("Removing \"{}\" ...", manifest_path.display())));
+        std::fs::remove_file(&manifest_path)?;
+        self.collect_garbage(tx)
+    }
+}
+
+/// Hex encoded SHA-256 of the contents of `path`.
+fn hash_file(path: &Path) -> std::io::Result<String> {
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::mpsc::channel, time::Duration};
+
+    use super::DedupBackend;
+    use crate::{storage::StorageBackend, test_util::fake_game};
+
+    #[test]
+    fn removing_waits_for_the_snapshots_being_stored() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = fake_game(&dir);
+        let backend = DedupBackend::new(dir.path().join("store"));
+        std::fs::create_dir(&backend.root).unwrap();
+        let (tx, rx) = channel();
+        backend.put_snapshot(&tx, &path).unwrap();
+        let snapshot = backend.list_snapshots(&path).unwrap().remove(0);
+
+        // As if another process was storing a snapshot
+        let storing = backend.lock(&tx, true).unwrap();
+        let (root, removing_tx) = (backend.root.clone(), tx.clone());
+        let removing = std::thread::spawn(move || {
+            DedupBackend::new(root).delete_snapshot(&removing_tx, &path, &snapshot)
+        });
+        std::thread::sleep(Duration::from_millis(200));
+        assert!(!removing.is_finished());
+        drop(storing);
+        removing.join().unwrap().unwrap();
+        drop(tx);
+        assert!(rx.iter().any(|log| log.to_string().starts_with("Waiting for another save-saver")));
+        let objects = walkdir::WalkDir::new(backend.objects_dir()).into_iter().filter_map(Result::ok);
+        assert_eq!(objects.filter(|entry| entry.file_type().is_file()).count(), 0);
+    }
+}
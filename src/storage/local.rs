@@ -0,0 +1,81 @@
+use std::path::{Path, PathBuf};
+
+use walkdir::WalkDir;
+
+use crate::{paths::BackupPath, log::{Log, LogSender, send}, error::Error, backup::{copy_folder_files_to_folder, copy_files_to_folder}};
+use super::{StorageBackend, Snapshot, new_snapshot_id, snapshot_date, check_root};
+
+/// Suffix of a snapshot that's still being copied.
+const PARTIAL: &str = ".partial";
+
+/// Stores every snapshot as a plain folder in `<root>/<branch_name>/<timestamp>`,
+/// e.g. on an external drive or a NAS mount.
+pub struct LocalBackend {
+    pub root: PathBuf
+}
+impl LocalBackend {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+    fn game_dir(&self, path: &BackupPath) -> PathBuf {
+        self.root.join(&path.branch_name)
+    }
+    /// Stores only `files` of `path`, by their path relative to it, as a new snapshot.
+    pub fn put_files(&self, tx: &LogSender, path: &BackupPath, files: Vec<(String, PathBuf)>) -> Result<(), Error> {
+        check_root(&self.root)?;
+        let id = new_snapshot_id();
+        let dir = self.game_dir(path);
+        let partial = dir.join(format!("{id}{PARTIAL}"));
+        send(tx, Log::step(format!("Copying files from \"{}\" to \"{}\" ...", path.absolute_path.display(), dir.join(&id).display())));
+        std::fs::create_dir_all(&dir)?;
+        if let Err(e) = copy_files_to_folder(tx, files, &partial) {
+            std::fs::remove_dir_all(&partial).ok();
+            return Err(e)
+        }
+        std::fs::rename(&partial, dir.join(&id))?;
+        Ok(())
+    }
+}
+impl StorageBackend for LocalBackend {
+    fn put_snapshot(&self, tx: &LogSender, path: &BackupPath) -> Result<(), Error> {
+        self.put_files(tx, path, path.files()?)
+    }
+    fn list_snapshots(&self, path: &BackupPath) -> Result<Vec<Snapshot>, Error> {
+        let dir = match std::fs::read_dir(self.game_dir(path)) { Ok(v) => v, Err(_) => return Ok(Vec::new()) };
+        let mut snapshots: Vec<Snapshot> = dir.filter_map(|entry| {
+            let entry = entry.ok()?;
+            let id = entry.file_name().into_string().ok()?;
+            let date = snapshot_date(&id)?;
+            let (mut files, mut size) = (0, 0);
+            for file in WalkDir::new(entry.path()).into_iter().filter_map(Result::ok) {
+                if file.file_type().is_file() {
+                    files += 1;
+                    size += file.metadata().map(|metadata| metadata.len()).unwrap_or_default();
+                }
+            }
+            Some(Snapshot {
+                date,
+                message: "Local snapshot".to_string(),
+                id,
+                files,
+                size
+            })
+        }).collect();
+        snapshots.sort_by(|a, b| b.id.cmp(&a.id));
+        Ok(snapshots)
+    }
+    fn fetch_snapshot(&self, tx: &LogSender, path: &BackupPath, snapshot: &Snapshot, to: &Path) -> Result<(), Error> {
+        let from = self.game_dir(path).join(&snapshot.id);
+        if !from.is_dir() {
+            return Err(Error::SnapshotNotFound(snapshot.id.clone()))
+        }
+        send(tx, Log::step(format!("Copying \"{}\" to \"{}\" ...", from.display(), to.display())));
+        copy_folder_files_to_folder(tx, &from, to)
+    }
+    fn delete_snapshot(&self, tx: &LogSender, path: &BackupPath, snapshot: &Snapshot) -> Result<(), Error> {
+        let dir = self.game_dir(path).join(&snapshot.id);
+        send(tx, Log::step(format!("Removing \"{}\" ...", dir.display())));
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
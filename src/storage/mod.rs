@@ -0,0 +1,88 @@
+use std::path::{Component, Path, PathBuf};
+
+use chrono::{NaiveDateTime, Utc};
+
+use crate::{paths::BackupPath, log::LogSender, error::Error};
+
+mod git;
+mod local;
+mod dedup;
+mod s3;
+
+pub use git::{GitBackend, GitError};
+pub use local::LocalBackend;
+pub use dedup::DedupBackend;
+pub use s3::{S3Backend, S3Config};
+
+/// A stored version of a game's saves, newest first when listed.
+#[derive(Clone)]
+pub struct Snapshot {
+    pub id: String,
+    pub date: String,
+    pub message: String,
+    pub files: usize,
+    pub size: u64
+}
+
+/// Ids of the snapshots of the backends that name them after when they were taken, e.g. "2024-03-02T18-30-00.000".
+const ID_FORMAT: &str = "%Y-%m-%dT%H-%M-%S%.3f";
+
+/// The id of a snapshot taken now, ids made this way sort like their dates.
+fn new_snapshot_id() -> String {
+    Utc::now().format(ID_FORMAT).to_string()
+}
+
+/// The date of a snapshot id made by `new_snapshot_id`, or `None` if it isn't one.
+fn snapshot_date(id: &str) -> Option<String> {
+    let date = NaiveDateTime::parse_from_str(id, ID_FORMAT).ok()?;
+    Some(date.format("%Y-%m-%dT%H:%M:%S+00:00").to_string())
+}
+
+/// Fails if the `root` folder of a backend is missing.
+fn check_root(root: &Path) -> Result<(), Error> {
+    // Don't create it, if it's missing the drive is probably just not mounted
+    if !root.is_dir() {
+        return Err(Error::BackupFolderNotFound(root.to_path_buf()))
+    }
+    Ok(())
+}
+
+/// Whether joining `relative` to a folder gives a path inside it.
+pub fn stays_inside(relative: &str) -> bool {
+    Path::new(relative).components().all(|component| matches!(component, Component::Normal(_)))
+}
+
+/// Where the file stored as `relative` in a snapshot goes in `to`, refusing paths that would lead out of it.
+/// Those can only come from a tampered store, the backends never write them.
+fn join_inside(to: &Path, relative: &str) -> Result<PathBuf, Error> {
+    if relative.is_empty() || !stays_inside(relative) { return Err(Error::UnsafePath(relative.to_string())) }
+    Ok(to.join(relative))
+}
+
+/// Somewhere snapshots of every `BackupPath` can be stored and read back from.
+///
+/// Implementations must be usable from the background threads and report progress through the given `LogSender`.
+/// Every step stops at the first failure and returns it, the caller decides whether to go on with other games.
+pub trait StorageBackend: Send + Sync {
+    /// Called once before a batch of `put_snapshot`, e.g. to update an index of all paths.
+    fn prepare(&self, _tx: &LogSender, _paths: &[BackupPath]) -> Result<(), Error> { Ok(()) }
+    /// Stores the current contents of `path.absolute_path` as a new snapshot.
+    fn put_snapshot(&self, tx: &LogSender, path: &BackupPath) -> Result<(), Error>;
+    /// Lists every snapshot stored for `path`, newest first.
+    fn list_snapshots(&self, path: &BackupPath) -> Result<Vec<Snapshot>, Error>;
+    /// Writes the files of `snapshot` into `to`, replacing its contents.
+    fn fetch_snapshot(&self, tx: &LogSender, path: &BackupPath, snapshot: &Snapshot, to: &Path) -> Result<(), Error>;
+    /// Removes `snapshot`, keeping every other snapshot of `path`.
+    fn delete_snapshot(&self, tx: &LogSender, path: &BackupPath, snapshot: &Snapshot) -> Result<(), Error>;
+}
+
+/// Finds a snapshot by index (0 is the newest), id prefix or date prefix.
+pub fn find_snapshot<'a>(snapshots: &'a [Snapshot], query: &str) -> Option<&'a Snapshot> {
+    let query = query.trim();
+    if query.is_empty() { return None }
+    if let Ok(index) = query.parse::<usize>() {
+        if let Some(snapshot) = snapshots.get(index) { return Some(snapshot) }
+    }
+    snapshots.iter().find(|snapshot| snapshot.id.starts_with(query))
+        .or_else(|| snapshots.iter().find(|snapshot| snapshot.date.starts_with(query)))
+}
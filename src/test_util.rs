@@ -0,0 +1,32 @@
+//! Helpers shared by the unit tests.
+
+use std::{collections::BTreeMap, path::Path};
+
+use tempfile::TempDir;
+
+use crate::paths::BackupPath;
+
+/// Writes `content` to `file` in `root`, creating its folders.
+pub fn write(root: &Path, file: &str, content: &[u8]) {
+    let path = root.join(file);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(path, content).unwrap();
+}
+
+/// Every file under `root` with its content.
+pub fn read_tree(root: &Path) -> BTreeMap<String, Vec<u8>> {
+    walkdir::WalkDir::new(root).into_iter()
+        .map(Result::unwrap)
+        .filter(|entry| entry.file_type().is_file())
+        .map(|entry| (crate::diff::relative_name(entry.path(), root), std::fs::read(entry.path()).unwrap()))
+        .collect()
+}
+
+/// A save folder with a few files in `dir/saves`.
+pub fn fake_game(dir: &TempDir) -> BackupPath {
+    let saves = dir.path().join("saves");
+    write(&saves, "slot1.sav", b"first slot");
+    write(&saves, "profiles/player.dat", &[0, 159, 146, 150, 255]);
+    write(&saves, "profiles/empty", b"");
+    BackupPath::new("Test Game", saves)
+}
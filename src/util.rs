@@ -0,0 +1,22 @@
+//! Small helpers shared by the tabs, the CLI and the logs.
+
+/// How many rows to skip so the selected one stays in the middle of a list `height` rows high.
+pub fn scroll_offset(selected: usize, length: usize, height: usize) -> usize {
+    if length <= height { return 0 }
+    let h2 = height / 2;
+    if selected <= h2 { 0 }
+    else if selected < length - h2 { selected - h2 }
+    else { length - height }
+}
+
+pub fn format_size(size: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut size = size as f64;
+    let mut unit = 0;
+    while size >= 1024. && unit < UNITS.len() - 1 {
+        size /= 1024.;
+        unit += 1;
+    }
+    if unit == 0 { format!("{size} {}", UNITS[unit]) }
+    else { format!("{size:.1} {}", UNITS[unit]) }
+}
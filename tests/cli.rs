@@ -0,0 +1,291 @@
+//! Runs the `save-saver` binary against a local bare repo, with a temp folder as `$HOME`.
+
+mod common;
+
+use std::{path::{Path, PathBuf}, process::{Command, Output}};
+
+use git2::Repository;
+use tempfile::TempDir;
+
+use common::{write, read_tree};
+
+/// A fake home folder whose "conf.txt" points to a local bare repo, and an empty working directory.
+struct Sandbox {
+    dir: TempDir
+}
+impl Sandbox {
+    fn new() -> Self {
+        let sandbox = Self { dir: tempfile::tempdir().unwrap() };
+        std::fs::create_dir_all(sandbox.config()).unwrap();
+        std::fs::create_dir_all(sandbox.work()).unwrap();
+        Repository::init_bare(sandbox.remote()).unwrap();
+        std::fs::write(sandbox.config().join("conf.txt"), sandbox.conf()).unwrap();
+        sandbox
+    }
+    fn conf(&self) -> String {
+        format!("repo_url = {}\ngit_user_name = Tester\ngit_user_email = tester@example.com\n", self.remote().display())
+    }
+    fn home(&self) -> PathBuf { self.dir.path().join("home") }
+    fn config(&self) -> PathBuf { self.home().join(".config/save-saver") }
+    fn work(&self) -> PathBuf { self.dir.path().join("work") }
+    fn remote(&self) -> PathBuf { self.dir.path().join("remote.git") }
+    /// A save folder in the fake home with a few files, added as "My Game".
+    fn add_game(&self) -> PathBuf {
+        let saves = self.home().join("games/my-game/saves");
+        write(&saves, "slot1.sav", b"first slot");
+        write(&saves, "profiles/player.dat", &[0, 159, 146, 150, 255]);
+        let output = self.run(&["add", "My Game", saves.to_str().unwrap()]);
+        assert!(output.status.success(), "{}", stderr(&output));
+        saves
+    }
+    fn run(&self, args: &[&str]) -> Output {
+        self.command(args).output().unwrap()
+    }
+    fn command(&self, args: &[&str]) -> Command {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_save-saver"));
+        command.args(args)
+            .current_dir(self.work())
+            .env("HOME", self.home())
+            .env_remove("XDG_DATA_HOME")
+            .env_remove("XDG_CONFIG_HOME")
+            .env_remove("XDG_CACHE_HOME")
+            // No prompt can be answered, a restore that needs one is refused
+            .stdin(std::process::Stdio::null());
+        command
+    }
+    /// Runs a command that has to succeed, returning what it printed.
+    fn ok(&self, args: &[&str]) -> String {
+        let output = self.run(args);
+        assert!(output.status.success(), "{args:?} failed:\n{}{}", stdout(&output), stderr(&output));
+        stdout(&output)
+    }
+    /// Number of commits on `branch` in the remote.
+    fn commits(&self, branch: &str) -> usize {
+        let repo = Repository::open_bare(self.remote()).unwrap();
+        let head = repo.find_branch(branch, git2::BranchType::Local).unwrap().get().peel_to_commit().unwrap();
+        let mut walk = repo.revwalk().unwrap();
+        walk.push(head.id()).unwrap();
+        walk.count()
+    }
+}
+
+fn stdout(output: &Output) -> String { String::from_utf8_lossy(&output.stdout).to_string() }
+fn stderr(output: &Output) -> String { String::from_utf8_lossy(&output.stderr).to_string() }
+
+#[test]
+fn add_stores_paths_relative_to_home() {
+    let sandbox = Sandbox::new();
+    let saves = sandbox.add_game();
+    let paths = std::fs::read_to_string(sandbox.config().join("paths.txt")).unwrap();
+    assert!(paths.contains("My Game = $HOME/games/my-game/saves"), "{paths}");
+    assert!(sandbox.ok(&["list"]).contains(&format!("My Game = {}", saves.display())));
+}
+
+#[test]
+fn backup_pushes_the_files_to_the_branch() {
+    let sandbox = Sandbox::new();
+    sandbox.add_game();
+    sandbox.ok(&["backup"]);
+
+    let repo = Repository::open_bare(sandbox.remote()).unwrap();
+    let commit = repo.find_branch("my-game", git2::BranchType::Local).unwrap().get().peel_to_commit().unwrap();
+    assert_eq!(commit.author().name(), Some("Tester"));
+    let tree = commit.tree().unwrap();
+    let blob = tree.get_path(Path::new("content/slot1.sav")).unwrap().to_object(&repo).unwrap().peel_to_blob().unwrap();
+    assert_eq!(blob.content(), b"first slot");
+    assert!(tree.get_path(Path::new("content/profiles/player.dat")).is_ok());
+
+    // Nothing is left behind in the working directory
+    assert_eq!(std::fs::read_dir(sandbox.work()).unwrap().count(), 0);
+}
+
+#[test]
+fn backup_keeps_the_other_files_of_master() {
+    let sandbox = Sandbox::new();
+    let repo = Repository::open_bare(sandbox.remote()).unwrap();
+    let mut builder = repo.treebuilder(None).unwrap();
+    builder.insert("LICENSE", repo.blob(b"MIT").unwrap(), 0o100644).unwrap();
+    let tree = repo.find_tree(builder.write().unwrap()).unwrap();
+    let signature = git2::Signature::now("Owner", "owner@example.com").unwrap();
+    repo.commit(Some("refs/heads/master"), &signature, &signature, "License", &tree, &[]).unwrap();
+
+    sandbox.add_game();
+    sandbox.ok(&["backup"]);
+
+    let master = repo.find_branch("master", git2::BranchType::Local).unwrap().get().peel_to_commit().unwrap();
+    let tree = master.tree().unwrap();
+    let blob = |name: &str| tree.get_name(name).unwrap().to_object(&repo).unwrap().peel_to_blob().unwrap().content().to_vec();
+    assert_eq!(blob("LICENSE"), b"MIT");
+    assert!(String::from_utf8(blob("README.md")).unwrap().contains("My Game = "));
+    assert_eq!(master.parent_count(), 1);
+}
+
+#[test]
+fn config_in_the_current_directory_is_copied() {
+    let sandbox = Sandbox::new();
+    std::fs::remove_file(sandbox.config().join("conf.txt")).unwrap();
+    std::fs::write(sandbox.work().join("conf.txt"), sandbox.conf()).unwrap();
+    std::fs::write(sandbox.work().join("paths.txt"), "Old Game = /saves/old-game\n").unwrap();
+
+    let output = sandbox.run(&["list"]);
+    assert!(stderr(&output).contains("Copied"), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Old Game = /saves/old-game"));
+    assert!(sandbox.work().join("paths.txt").exists() && sandbox.work().join("conf.txt").exists());
+    assert_eq!(std::fs::read_to_string(sandbox.config().join("conf.txt")).unwrap(), sandbox.conf());
+
+    // An existing config isn't replaced
+    std::fs::write(sandbox.work().join("paths.txt"), "Stray = /saves/stray\n").unwrap();
+    assert!(!sandbox.ok(&["list"]).contains("Stray"));
+
+    // Nor is anything copied to a config folder given with "--config"
+    let other = sandbox.dir.path().join("other-config");
+    let output = sandbox.run(&["--config", other.to_str().unwrap(), "list"]);
+    assert!(stderr(&output).is_empty(), "{}", stderr(&output));
+    assert_eq!(std::fs::read_to_string(other.join("paths.txt")).unwrap(), "");
+}
+
+#[test]
+fn config_flag_overrides_the_config_folder() {
+    let sandbox = Sandbox::new();
+    let other = sandbox.dir.path().join("other-config");
+    std::fs::create_dir_all(&other).unwrap();
+    std::fs::write(other.join("paths.txt"), "Elsewhere = /saves/elsewhere\n").unwrap();
+    let listed = sandbox.ok(&["--config", other.to_str().unwrap(), "list"]);
+    assert!(listed.contains("Elsewhere"), "{listed}");
+    assert!(!sandbox.config().join("paths.txt").exists());
+}
+
+#[test]
+fn xdg_variables_move_the_folders() {
+    let sandbox = Sandbox::new();
+    let saves = sandbox.add_game();
+    sandbox.ok(&["backup"]);
+    let (config, data) = (sandbox.dir.path().join("xdg-config"), sandbox.dir.path().join("xdg-data"));
+    std::fs::create_dir_all(&config).unwrap();
+    std::fs::rename(sandbox.config(), config.join("save-saver")).unwrap();
+    write(&saves, "slot1.sav", b"corrupt");
+
+    let output = sandbox.command(&["restore", "--yes"])
+        .env("XDG_CONFIG_HOME", &config)
+        .env("XDG_DATA_HOME", &data)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(std::fs::read(saves.join("slot1.sav")).unwrap(), b"first slot");
+    assert!(data.join("save-saver/pre-restore/my-game").is_dir());
+    assert!(!sandbox.home().join(".local").exists());
+}
+
+#[test]
+fn backup_without_changes_adds_no_commit() {
+    let sandbox = Sandbox::new();
+    let saves = sandbox.add_game();
+    sandbox.ok(&["backup"]);
+    assert!(sandbox.ok(&["backup"]).contains("Nothing changed"));
+    assert_eq!(sandbox.commits("my-game"), 1);
+
+    write(&saves, "slot1.sav", b"played some more");
+    sandbox.ok(&["backup"]);
+    assert_eq!(sandbox.commits("my-game"), 2);
+}
+
+#[test]
+fn restore_puts_back_the_backed_up_files() {
+    let sandbox = Sandbox::new();
+    let saves = sandbox.add_game();
+    let original = read_tree(&saves);
+    sandbox.ok(&["backup"]);
+
+    write(&saves, "slot1.sav", b"corrupt");
+    std::fs::remove_file(saves.join("profiles/player.dat")).unwrap();
+    write(&saves, "crash.dmp", b"not backed up");
+    let changed = read_tree(&saves);
+
+    // Files would be overwritten and nobody can confirm
+    assert!(!sandbox.run(&["restore"]).status.success());
+    sandbox.ok(&["restore", "--dry-run"]);
+    assert_eq!(read_tree(&saves), changed);
+
+    sandbox.ok(&["restore", "--yes"]);
+    assert_eq!(read_tree(&saves), original);
+    assert!(sandbox.home().join(".local/share/save-saver/pre-restore/my-game").is_dir());
+
+    sandbox.ok(&["rollback"]);
+    assert_eq!(read_tree(&saves), changed);
+}
+
+#[test]
+fn restore_picks_the_snapshot_given() {
+    let sandbox = Sandbox::new();
+    let saves = sandbox.add_game();
+    sandbox.ok(&["backup"]);
+    write(&saves, "slot1.sav", b"played some more");
+    sandbox.ok(&["backup"]);
+
+    let listed = sandbox.ok(&["--json", "snapshots", "My Game"]);
+    let snapshots: Vec<serde_json::Value> = listed.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+    assert_eq!(snapshots.len(), 2, "{listed}");
+    assert_eq!(snapshots[1]["index"], 1);
+    assert!(sandbox.ok(&["snapshots", "My Game"]).lines().nth(1).unwrap().starts_with("  1  "));
+
+    // The older snapshot, by index for "files" and by id for "restore"
+    assert!(sandbox.ok(&["files", "My Game", "--snapshot", "1"]).contains("slot1.sav  10 B"));
+    let id = snapshots[1]["id"].as_str().unwrap();
+    sandbox.ok(&["restore", "--game", "My Game", "--snapshot", &id[..8], "--yes"]);
+    assert_eq!(std::fs::read(saves.join("slot1.sav")).unwrap(), b"first slot");
+
+    assert!(!sandbox.run(&["restore", "--snapshot", "1999-01-01", "--yes"]).status.success());
+    assert!(!sandbox.run(&["files", "My Game", "--snapshot", "no-such-snapshot"]).status.success());
+}
+
+#[test]
+fn compress_zips_every_game() {
+    let sandbox = Sandbox::new();
+    sandbox.add_game();
+    sandbox.ok(&["compress"]);
+    let zip = zip::ZipArchive::new(std::fs::File::open(sandbox.work().join("saves.zip")).unwrap()).unwrap();
+    let mut names: Vec<&str> = zip.file_names().collect();
+    names.sort();
+    assert_eq!(names.len(), 2);
+    assert!(names[0].ends_with("profiles/player.dat") && names[1].ends_with("slot1.sav"), "{names:?}");
+}
+
+#[test]
+fn json_prints_only_json_lines() {
+    let sandbox = Sandbox::new();
+    let saves = sandbox.home().join("games/my-game/saves");
+    write(&saves, "slot1.sav", b"first slot");
+    let commands: [&[&str]; 4] = [
+        &["--json", "add", "My Game", saves.to_str().unwrap()],
+        &["--json", "backup"],
+        &["--json", "compress"],
+        &["--json", "remove", "My Game"]
+    ];
+    for args in commands {
+        let printed = sandbox.ok(args);
+        assert!(!printed.is_empty(), "{args:?}");
+        for line in printed.lines() {
+            assert!(serde_json::from_str::<serde_json::Value>(line).is_ok(), "{args:?} printed {line:?}");
+        }
+    }
+}
+
+#[test]
+fn unknown_backend_is_refused() {
+    let sandbox = Sandbox::new();
+    sandbox.add_game();
+    std::fs::write(sandbox.config().join("conf.txt"), format!("{}backend = ftp\n", sandbox.conf())).unwrap();
+    let output = sandbox.run(&["backup"]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("line 4: unknown backend \"ftp\""), "{}", stderr(&output));
+}
+
+#[test]
+fn unreachable_remote_fails_the_backup() {
+    let sandbox = Sandbox::new();
+    sandbox.add_game();
+    std::fs::remove_dir_all(sandbox.remote()).unwrap();
+    let output = sandbox.run(&["backup"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stdout(&output).contains("Skipped: My Game"), "{}", stdout(&output));
+}
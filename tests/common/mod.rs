@@ -0,0 +1,19 @@
+//! Helpers shared by the integration tests.
+
+use std::{collections::BTreeMap, path::{Path, PathBuf}};
+
+/// Writes `content` to `file` in `root`, creating its folders.
+pub fn write(root: &Path, file: &str, content: &[u8]) {
+    let path = root.join(file);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(path, content).unwrap();
+}
+
+/// Every file under `root` with its content.
+pub fn read_tree(root: &Path) -> BTreeMap<PathBuf, Vec<u8>> {
+    walkdir::WalkDir::new(root).into_iter()
+        .map(Result::unwrap)
+        .filter(|entry| entry.file_type().is_file())
+        .map(|entry| (entry.path().strip_prefix(root).unwrap().to_path_buf(), std::fs::read(entry.path()).unwrap()))
+        .collect()
+}